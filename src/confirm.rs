@@ -0,0 +1,278 @@
+use boa_engine::{Context, Source};
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::html::{tokenize, TextKind, Token};
+use crate::js::statement_at;
+use crate::payloads::Payload;
+use crate::reflection::Signature;
+
+// names the stub DOM gives up on after this many reference errors
+const MAX_UNDEFINED_NAMES: usize = 32;
+const LOOP_ITERATION_LIMIT: u64 = 100_000;
+const RECURSION_LIMIT: usize = 256;
+
+// code longer than this is not run at all
+const MAX_SCRIPT_LEN: usize = 16 * 1024;
+
+// how many reflections of the payload's code in one script are tried
+const MAX_STATEMENTS: usize = 8;
+
+/// How long [`executes`] may spend running code for one response.
+pub const CONFIRM_TIMEOUT: Duration = Duration::from_secs(2);
+
+// a minimal browser environment: the dialog functions call the marker, and
+// every other DOM object is a proxy that accepts any property access or call
+const DOM_STUB: &str = r#"
+var __crabxss_calls = [];
+function crabxss_marker(value) { __crabxss_calls.push(String(value)); }
+var alert = crabxss_marker, confirm = crabxss_marker, prompt = crabxss_marker, print = crabxss_marker;
+var __crabxss_stub = (function () {
+    var stub;
+    var empty = function () { return ''; };
+    stub = new Proxy(function () {}, {
+        get: function (target, name) {
+            if (name === Symbol.toPrimitive || name === 'toString' || name === 'valueOf') return empty;
+            if (name === 'then') return undefined;
+            return stub;
+        },
+        set: function () { return true; },
+        apply: function () { return stub; },
+        construct: function () { return stub; }
+    });
+    return stub;
+})();
+var window = globalThis, self = globalThis, top = globalThis, parent = globalThis, frames = globalThis;
+var document = __crabxss_stub, location = __crabxss_stub, navigator = __crabxss_stub, history = __crabxss_stub;
+var localStorage = __crabxss_stub, sessionStorage = __crabxss_stub, console = __crabxss_stub;
+var $ = __crabxss_stub, jQuery = __crabxss_stub;
+function setTimeout(f) { typeof f === 'function' ? f() : (0, eval)(String(f)); return 1; }
+var setInterval = setTimeout, requestAnimationFrame = setTimeout;
+function addEventListener() {}
+"#;
+
+/// Whether `payload` actually runs when `body` is loaded.
+///
+/// The statement around each reflection of the payload's code in a script
+/// block, and every event handler and `javascript:` URL that contains it, is
+/// run in an embedded JavaScript engine against a stub DOM. The payload is
+/// confirmed when it calls `crabxss_marker`, which `alert`, `confirm`,
+/// `prompt` and `print` are routed to, with the number its own code passes
+/// to them, so the page's own dialogs don't count.
+///
+/// This is CPU-bound and may take up to [`CONFIRM_TIMEOUT`], so async
+/// callers should run it on a blocking thread. Code longer than 16 KiB is
+/// skipped.
+pub fn executes(body: &str, payload: &Payload) -> bool {
+    let codes = payload_code(payload);
+    if codes.is_empty() {
+        return false;
+    }
+    let markers = markers(&codes);
+    let deadline = Instant::now() + CONFIRM_TIMEOUT;
+    let contains_code = |text: &str| codes.iter().any(|code| text.contains(code.as_str()));
+    let runs_marker = |script: &str| runs_marker(script, &markers, deadline);
+
+    tokenize(body).iter().any(|token| match token {
+        Token::Text {
+            text,
+            kind: TextKind::Script,
+        } => codes
+            .iter()
+            .flat_map(|code| text.match_indices(code.as_str()))
+            .take(MAX_STATEMENTS)
+            .any(|(pos, _)| runs_marker(statement_at(text, pos))),
+        Token::StartTag { attrs, .. } => attrs.iter().any(|(name, value)| {
+            if name.starts_with("on") {
+                if !contains_code(value) {
+                    return false;
+                }
+                // handlers run as a function body with `this` and `event` set
+                let script = format!(
+                    "(function (event) {{\n{}\n}}).call(__crabxss_stub, __crabxss_stub);",
+                    value
+                );
+                return runs_marker(&script);
+            }
+            // URLs are percent-decoded before the code is looked for
+            match javascript_url(value) {
+                Some(script) if contains_code(&script) => runs_marker(&script),
+                _ => false,
+            }
+        }),
+        _ => false,
+    })
+}
+
+// the code each signature of `payload` expects to run
+fn payload_code(payload: &Payload) -> Vec<String> {
+    let mut codes = Vec::new();
+    for signature in &payload.signatures {
+        match signature {
+            Signature::Element { name, attrs, text } => {
+                codes.extend(
+                    attrs
+                        .iter()
+                        .filter(|(name, value)| name.starts_with("on") && !value.is_empty())
+                        .map(|(_, value)| value.clone()),
+                );
+                // a new `<script>` runs its own text
+                if name == "script" {
+                    codes.extend(text.iter().filter(|text| !text.is_empty()).cloned());
+                }
+            }
+            Signature::Handler { value, .. } => codes.push(value.clone()),
+            Signature::JavascriptUrl { code, .. } | Signature::ScriptCode { code } => codes.push(code.clone()),
+        }
+    }
+    codes
+}
+
+// the numbers `codes` pass to a dialog, which the marker must be called
+// with; empty when they pass anything else, and then any call counts
+fn markers(codes: &[String]) -> Vec<String> {
+    codes
+        .iter()
+        .flat_map(|code| dialog_call().captures_iter(code))
+        .filter_map(|cap| cap.get(1).or_else(|| cap.get(2)))
+        .map(|arg| arg.as_str().trim().to_owned())
+        .filter(|arg| !arg.is_empty() && arg.chars().all(|c| c.is_ascii_digit()))
+        .collect()
+}
+
+// the script of a `javascript:` URL, percent-decoded like a browser does
+fn javascript_url(value: &str) -> Option<String> {
+    let value = value.trim_start();
+    let scheme = value.get(..11)?;
+    if !scheme.eq_ignore_ascii_case("javascript:") {
+        return None;
+    }
+    Some(percent_decode_str(&value[11..]).decode_utf8_lossy().into_owned())
+}
+
+// runs `script` after the DOM stub and reports whether the marker was
+// called with one of `markers`, or at all when there are none; names the
+// stub lacks are defined as stubs and the script rerun, until `deadline`
+fn runs_marker(script: &str, markers: &[String], deadline: Instant) -> bool {
+    if script.len() > MAX_SCRIPT_LEN {
+        return false;
+    }
+    let mut undefined: Vec<String> = Vec::new();
+
+    loop {
+        if Instant::now() >= deadline {
+            return false;
+        }
+
+        let mut context = Context::default();
+        context.runtime_limits_mut().set_loop_iteration_limit(LOOP_ITERATION_LIMIT);
+        context.runtime_limits_mut().set_recursion_limit(RECURSION_LIMIT);
+
+        let mut prelude = DOM_STUB.to_owned();
+        for name in &undefined {
+            prelude.push_str(&format!("var {} = __crabxss_stub;\n", name));
+        }
+        if context.eval(Source::from_bytes(&prelude)).is_err() {
+            return false;
+        }
+
+        let outcome = context.eval(Source::from_bytes(script));
+        let _ = context.run_jobs();
+
+        let calls: Vec<String> = context
+            .eval(Source::from_bytes("JSON.stringify(__crabxss_calls)"))
+            .ok()
+            .and_then(|calls| calls.as_string().map(|calls| calls.to_std_string_escaped()))
+            .and_then(|calls| serde_json::from_str(&calls).ok())
+            .unwrap_or_default();
+        if calls.iter().any(|call| markers.is_empty() || markers.contains(call)) {
+            return true;
+        }
+
+        let Err(error) = outcome else {
+            return false;
+        };
+        let message = error.to_string();
+        match reference_error().captures(&message) {
+            Some(cap) if undefined.len() < MAX_UNDEFINED_NAMES && !undefined.contains(&cap[1].to_owned()) => {
+                undefined.push(cap[1].to_owned());
+            }
+            _ => return false,
+        }
+    }
+}
+
+fn dialog_call() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"\b(?:alert|confirm|prompt|print)\s*(?:\(([^()]*)\)|`([^`]*)`)").expect("invalid dialog call pattern")
+    })
+}
+
+fn reference_error() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"ReferenceError: ([A-Za-z_$][\w$]*) is not defined").expect("invalid reference error pattern")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(code: &str) -> Payload {
+        Payload {
+            value: code.to_owned(),
+            signatures: vec![Signature::ScriptCode { code: code.to_owned() }],
+        }
+    }
+
+    #[test]
+    fn confirms_handler_breakouts() {
+        let payload = Payload::custom(r#"" autofocus onfocus=alert(424242) x=""#);
+        let body = r#"<input value="" autofocus onfocus=alert(424242) x="">"#;
+        assert!(executes(body, &payload));
+
+        let quoted = r#"<input value="&quot; autofocus onfocus=alert(424242) x=&quot;">"#;
+        assert!(!executes(quoted, &payload));
+    }
+
+    #[test]
+    fn confirms_javascript_urls() {
+        let payload = Payload {
+            value: "javascript:alert(424242)".to_owned(),
+            signatures: vec![Signature::JavascriptUrl {
+                attribute: "href".to_owned(),
+                code: "alert(424242)".to_owned(),
+            }],
+        };
+        assert!(executes(r#"<a href="javascript:alert%28424242%29">x</a>"#, &payload));
+        assert!(!executes(r#"<a href="/search?q=alert(424242)">x</a>"#, &payload));
+    }
+
+    #[test]
+    fn escaped_quotes_keep_the_payload_in_the_string() {
+        let payload = script("alert(424242)");
+        assert!(executes("<script>var q = '';alert(424242);//';</script>", &payload));
+        assert!(!executes("<script>var q = '\\';alert(424242);//';</script>", &payload));
+    }
+
+    #[test]
+    fn reruns_with_undefined_names_stubbed() {
+        let payload = script("alert(424242)");
+        let body = "<script>var q = ''-trackSearch(pageData)-alert(424242)-'';</script>";
+        assert!(executes(body, &payload));
+    }
+
+    #[test]
+    fn the_pages_own_dialogs_dont_count() {
+        let payload = script("alert(424242)");
+        let body = "<script>var q = 'alert(424242)'; alert(1);</script>";
+        assert!(!executes(body, &payload));
+        let body = r#"<button onclick="alert(1); log('alert(424242)')">x</button>"#;
+        assert!(!executes(body, &Payload::custom(r#"<b onclick=alert(424242)>"#)));
+        assert!(!executes(body, &payload));
+    }
+}
@@ -0,0 +1,163 @@
+use url::Url;
+
+/// A cookie, optionally scoped to a domain and path the way a browser
+/// scopes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// The domain the cookie is sent to, or `None` to send it everywhere.
+    pub domain: Option<String>,
+    /// Whether subdomains of `domain` get the cookie too.
+    pub include_subdomains: bool,
+    /// The path prefix the cookie is sent to.
+    pub path: String,
+    /// Whether the cookie is only sent over HTTPS.
+    pub secure: bool,
+}
+
+impl Cookie {
+    /// A cookie sent with every request.
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Cookie {
+            name: name.into(),
+            value: value.into(),
+            domain: None,
+            include_subdomains: false,
+            path: "/".to_owned(),
+            secure: false,
+        }
+    }
+
+    /// Whether a browser would send this cookie with a request to `url`.
+    pub fn matches(&self, url: &Url) -> bool {
+        if self.secure && url.scheme() != "https" {
+            return false;
+        }
+
+        if let Some(domain) = &self.domain {
+            let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+            let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+            let subdomain = self.include_subdomains && host.ends_with(&format!(".{}", domain));
+            if host != domain && !subdomain {
+                return false;
+            }
+        }
+
+        // a path matches itself and everything below it, as RFC 6265 says
+        let path = url.path();
+        path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')))
+    }
+}
+
+/// Splits a `Cookie` header value into its name/value pairs.
+pub fn parse_cookie_header(header: &str) -> Vec<(String, String)> {
+    header
+        .split(';')
+        .filter_map(|cookie| {
+            let (name, value) = cookie.split_once('=')?;
+            let name = name.trim();
+            (!name.is_empty()).then(|| (name.to_owned(), value.trim().to_owned()))
+        })
+        .collect()
+}
+
+/// Joins name/value pairs into a `Cookie` header value.
+pub fn format_cookie_header(cookies: &[(String, String)]) -> String {
+    cookies
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Encodes a value so it can't end the cookie it is in early.
+pub fn encode_cookie_value(value: &str) -> String {
+    value.replace(';', "%3B")
+}
+
+/// Parses a cookie file, either in the Netscape format written by curl and
+/// browser extensions, or with `name=value` pairs on each line.
+///
+/// Netscape cookies keep their domain, path and secure flag, so they are
+/// only sent where a browser would send them. `name=value` cookies are sent
+/// everywhere.
+pub fn parse_cookie_file(contents: &str) -> Vec<Cookie> {
+    let mut cookies = Vec::new();
+
+    for line in contents.lines() {
+        // curl marks HttpOnly cookies with a prefix that looks like a comment
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line).trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if let [domain, include_subdomains, path, secure, _expires, name, value] = fields.as_slice() {
+            cookies.push(Cookie {
+                domain: Some(domain.to_string()),
+                include_subdomains: include_subdomains.eq_ignore_ascii_case("TRUE"),
+                path: path.to_string(),
+                secure: secure.eq_ignore_ascii_case("TRUE"),
+                ..Cookie::new(*name, *value)
+            });
+        } else {
+            cookies.extend(
+                parse_cookie_header(line)
+                    .into_iter()
+                    .map(|(name, value)| Cookie::new(name, value)),
+            );
+        }
+    }
+
+    cookies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn parses_both_file_formats() {
+        let contents = "# Netscape HTTP Cookie File\n\
+            .example.com\tTRUE\t/\tFALSE\t0\tsid\tabc\n\
+            #HttpOnly_app.test\tFALSE\t/admin\tTRUE\t0\tadmin\t1\n\
+            theme=dark; lang=en\n";
+
+        let cookies = parse_cookie_file(contents);
+        assert_eq!(cookies.len(), 4);
+        assert_eq!(cookies[0].domain.as_deref(), Some(".example.com"));
+        assert!(cookies[0].include_subdomains);
+        assert_eq!((cookies[1].path.as_str(), cookies[1].secure), ("/admin", true));
+        assert_eq!(cookies[2], Cookie::new("theme", "dark"));
+        assert_eq!(cookies[3], Cookie::new("lang", "en"));
+    }
+
+    #[test]
+    fn matches_domain_path_and_scheme() {
+        let cookies = parse_cookie_file(
+            ".example.com\tTRUE\t/\tFALSE\t0\tsid\tabc\n\
+             app.test\tFALSE\t/admin\tTRUE\t0\tadmin\t1\n",
+        );
+        let (sid, admin) = (&cookies[0], &cookies[1]);
+
+        assert!(sid.matches(&url("http://example.com/")));
+        assert!(sid.matches(&url("https://www.EXAMPLE.com/x")));
+        assert!(!sid.matches(&url("https://notexample.com/")));
+        assert!(!sid.matches(&url("https://other.test/")));
+
+        assert!(admin.matches(&url("https://app.test/admin")));
+        assert!(admin.matches(&url("https://app.test/admin/users")));
+        assert!(!admin.matches(&url("https://app.test/administrator")));
+        assert!(!admin.matches(&url("http://app.test/admin")));
+        assert!(!admin.matches(&url("https://sub.app.test/admin")));
+
+        assert!(Cookie::new("theme", "dark").matches(&url("http://anywhere.test/")));
+    }
+}
@@ -0,0 +1,88 @@
+use regex::Regex;
+use std::sync::OnceLock;
+use url::Url;
+
+use crate::html::{tokenize, Token};
+use crate::inject::injection_points;
+use crate::request::RequestTemplate;
+
+// attributes whose value is a link worth following
+const LINK_ATTRIBUTES: [&str; 4] = ["href", "src", "action", "formaction"];
+
+// extensions of resources that never contain links
+const STATIC_EXTENSIONS: [&str; 20] = [
+    "png", "jpg", "jpeg", "gif", "svg", "ico", "webp", "bmp", "css", "woff", "woff2", "ttf", "eot", "otf", "pdf",
+    "zip", "gz", "mp3", "mp4", "webm",
+];
+
+// parameterized URLs in JavaScript string literals, e.g. '/search?q=' or
+// "https://example.com/api?id=1"
+const JS_URL_PATTERN: &str = r#"["'`]((?:https?://[^"'`\s<>?]+|\.{0,2}/[^"'`\s<>?]*|[\w\-./]+)\?[\w\-.~%\[\]]+=[^"'`\s<>]*)["'`]"#;
+
+/// Every link in `body`, resolved against `base`: the `href`, `src` and
+/// `action` attributes of its tags, and the parameterized URLs in its
+/// JavaScript string literals. Only `http` and `https` links are kept, and
+/// fragments are dropped.
+pub fn extract_links(body: &str, base: &Url) -> Vec<Url> {
+    let attributes = tokenize(body).into_iter().flat_map(|token| match token {
+        Token::StartTag { attrs, .. } => attrs
+            .into_iter()
+            .filter(|(name, _)| LINK_ATTRIBUTES.contains(&name.as_str()))
+            .map(|(_, value)| value)
+            .collect(),
+        _ => Vec::new(),
+    });
+    let literals = js_url_pattern()
+        .captures_iter(body)
+        .map(|cap| cap[1].to_owned())
+        .collect::<Vec<_>>();
+
+    let mut links = Vec::new();
+    for link in attributes.chain(literals) {
+        let Ok(mut url) = base.join(link.trim()) else {
+            continue;
+        };
+        if !matches!(url.scheme(), "http" | "https") {
+            continue;
+        }
+        url.set_fragment(None);
+        if !links.contains(&url) {
+            links.push(url);
+        }
+    }
+
+    links
+}
+
+/// Whether `url` may contain links, judging by its extension.
+pub fn is_crawlable(url: &Url) -> bool {
+    let extension = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+
+    !matches!(extension, Some(extension) if STATIC_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// Identifies an endpoint by its origin, path and parameter names, so URLs
+/// that only differ in parameter values are scanned once.
+pub fn endpoint_key(url: &Url) -> String {
+    let mut names: Vec<String> = url.query_pairs().map(|(name, _)| name.into_owned()).collect();
+    names.sort();
+    names.dedup();
+    format!("{}{}?{}", url.origin().ascii_serialization(), url.path(), names.join("&"))
+}
+
+/// Identifies a request by its method, endpoint and injection points, so
+/// links and forms that submit the same fields are scanned once.
+pub fn request_key(request: &RequestTemplate) -> String {
+    let mut points: Vec<String> = injection_points(request).iter().map(ToString::to_string).collect();
+    points.sort();
+    format!("{} {} {}", request.method, endpoint_key(&request.url), points.join(", "))
+}
+
+fn js_url_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(JS_URL_PATTERN).expect("invalid JavaScript URL pattern"))
+}
@@ -0,0 +1,257 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+use url::Url;
+
+use crate::html::{tokenize, TextKind, Token};
+use crate::result::Confidence;
+
+// properties that hold attacker-controlled data
+const SOURCE_PATTERN: &str = r"\b(?:document\.)?location\.(?:hash|search|href)\b|\bdocument\.(?:URL|documentURI|baseURI|referrer)\b|\bwindow\.name\b";
+
+// message event data, only a source in scripts that listen for messages
+const MESSAGE_DATA_PATTERN: &str = r"\b(?:e|ev|evt|event|msg|message)\.data\b";
+const MESSAGE_LISTENER_PATTERN: &str = r#"addEventListener\(\s*["']message["']|\bonmessage\s*="#;
+
+// calls that make tainted data safe to pass to a sink; whole words, so
+// `unescape(` doesn't count as `escape(`
+const SANITIZER_PATTERN: &str = r"\b(?:encodeURIComponent|encodeURI|escape|parseInt|Number)\s*\(|\bDOMPurify\b|sanitize";
+
+/// A flow of attacker-controlled data from a source into a sink, found by
+/// statically tracing a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomFlow {
+    /// The source the data comes from, e.g. `location.hash`.
+    pub source: String,
+    /// The sink the data reaches, e.g. `innerHTML`.
+    pub sink: String,
+    /// Where the script lives: `inline script #n` or the script URL.
+    pub script: String,
+    /// The statement containing the sink.
+    pub statement: String,
+    /// `Medium` when the source is passed to the sink directly, `Low` when
+    /// it goes through variables first.
+    pub confidence: Confidence,
+}
+
+/// The bodies of every inline `<script>` in `html`.
+pub fn inline_scripts(html: &str) -> Vec<String> {
+    let tokens = tokenize(html);
+    let mut scripts = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        if !token.is_start_tag("script") || token.attr("src").is_some() {
+            continue;
+        }
+        if let Some(Token::Text {
+            text,
+            kind: TextKind::Script,
+        }) = tokens.get(i + 1)
+        {
+            scripts.push(text.clone());
+        }
+    }
+
+    scripts
+}
+
+/// The URLs of every `<script src>` in `html` on the same host as `base`.
+pub fn linked_scripts(html: &str, base: &Url) -> Vec<Url> {
+    tokenize(html)
+        .iter()
+        .filter(|token| token.is_start_tag("script"))
+        .filter_map(|token| token.attr("src"))
+        .filter_map(|src| base.join(src).ok())
+        .filter(|url| url.host_str() == base.host_str())
+        .collect()
+}
+
+/// Traces the sources in `script` to its sinks.
+///
+/// This works statement by statement: variables assigned from a source, or
+/// from another tainted variable, are tainted in turn, and every sink whose
+/// argument mentions a source or a tainted variable is reported.
+pub fn find_flows(script: &str, location: &str) -> Vec<DomFlow> {
+    let re = patterns();
+    let listens_for_messages = re.message_listener.is_match(script);
+
+    let find_source = |expr: &str| -> Option<String> {
+        re.source.find(expr).map(|m| m.as_str().to_owned()).or_else(|| {
+            listens_for_messages
+                .then(|| re.message_data.find(expr).map(|m| m.as_str().to_owned()))
+                .flatten()
+        })
+    };
+
+    let statements: Vec<&str> = script
+        .split([';', '\n', '{', '}'])
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+        .collect();
+
+    // variable name -> the source it was tainted by
+    let mut tainted: HashMap<String, String> = HashMap::new();
+    loop {
+        let before = tainted.len();
+        for statement in &statements {
+            let Some(cap) = re.assignment.captures(statement) else {
+                continue;
+            };
+            let (name, value) = (&cap[1], &cap[2]);
+            if tainted.contains_key(name) || is_sanitized(value) {
+                continue;
+            }
+            if let Some(source) = find_source(value).or_else(|| tainted_source(value, &tainted, &re.identifier)) {
+                tainted.insert(name.to_owned(), source);
+            }
+        }
+        if tainted.len() == before {
+            break;
+        }
+    }
+
+    let mut flows = Vec::new();
+    let mut seen = HashSet::new();
+
+    for statement in &statements {
+        for (sink, pattern) in &re.sinks {
+            let Some(cap) = pattern.captures(statement) else {
+                continue;
+            };
+            let argument = &cap[1];
+            if is_sanitized(argument) || (sink.starts_with("set") && passes_function(argument)) {
+                continue;
+            }
+
+            let (source, confidence) = match find_source(argument) {
+                Some(source) => (source, Confidence::Medium),
+                None => match tainted_source(argument, &tainted, &re.identifier) {
+                    Some(source) => (source, Confidence::Low),
+                    None => continue,
+                },
+            };
+
+            if seen.insert((source.clone(), sink.to_string(), statement.to_string())) {
+                flows.push(DomFlow {
+                    source,
+                    sink: sink.to_string(),
+                    script: location.to_owned(),
+                    statement: statement.to_string(),
+                    confidence,
+                });
+            }
+        }
+    }
+
+    flows
+}
+
+// the source of the first tainted variable used in `expr`
+fn tainted_source(expr: &str, tainted: &HashMap<String, String>, identifier: &Regex) -> Option<String> {
+    identifier
+        .find_iter(expr)
+        .find_map(|name| tainted.get(name.as_str()).cloned())
+}
+
+fn is_sanitized(expr: &str) -> bool {
+    patterns().sanitizer.is_match(expr)
+}
+
+// whether the first argument of a timer is a function rather than a string
+fn passes_function(argument: &str) -> bool {
+    let first = argument.split(',').next().unwrap_or_default().trim_start();
+    first.starts_with("function") || first.contains("=>")
+}
+
+struct Patterns {
+    source: Regex,
+    message_data: Regex,
+    message_listener: Regex,
+    sanitizer: Regex,
+    assignment: Regex,
+    identifier: Regex,
+    sinks: Vec<(&'static str, Regex)>,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+
+    PATTERNS.get_or_init(|| {
+        let sink = |pattern: &str| Regex::new(pattern).expect("invalid sink pattern");
+        Patterns {
+            source: Regex::new(SOURCE_PATTERN).expect("invalid source pattern"),
+            message_data: Regex::new(MESSAGE_DATA_PATTERN).expect("invalid source pattern"),
+            message_listener: Regex::new(MESSAGE_LISTENER_PATTERN).expect("invalid source pattern"),
+            sanitizer: Regex::new(SANITIZER_PATTERN).expect("invalid sanitizer pattern"),
+            assignment: Regex::new(r"^(?:(?:var|let|const)\s+)?([A-Za-z_$][\w$]*)\s*=\s*([^=].*)$")
+                .expect("invalid assignment pattern"),
+            identifier: Regex::new(r"[A-Za-z_$][\w$]*").expect("invalid identifier pattern"),
+            sinks: vec![
+                ("innerHTML", sink(r"\.innerHTML\s*\+?=\s*([^=].*)")),
+                ("outerHTML", sink(r"\.outerHTML\s*\+?=\s*([^=].*)")),
+                ("document.write", sink(r"\bdocument\.write(?:ln)?\s*\((.*)")),
+                ("eval", sink(r"\beval\s*\((.*)")),
+                ("setTimeout", sink(r"\bsetTimeout\s*\((.*)")),
+                ("setInterval", sink(r"\bsetInterval\s*\((.*)")),
+                ("Function", sink(r"\bnew\s+Function\s*\((.*)")),
+                ("jQuery html()", sink(r"\.html\s*\(([^)].*)")),
+                ("insertAdjacentHTML", sink(r"\.insertAdjacentHTML\s*\((.*)")),
+            ],
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sinks(script: &str) -> Vec<(String, String, Confidence)> {
+        find_flows(script, "inline script #1")
+            .into_iter()
+            .map(|flow| (flow.source, flow.sink, flow.confidence))
+            .collect()
+    }
+
+    #[test]
+    fn finds_direct_flows() {
+        assert_eq!(
+            sinks("document.getElementById('x').innerHTML = location.hash;"),
+            [("location.hash".to_owned(), "innerHTML".to_owned(), Confidence::Medium)]
+        );
+        assert_eq!(
+            sinks("document.write(unescape(location.hash))"),
+            [("location.hash".to_owned(), "document.write".to_owned(), Confidence::Medium)]
+        );
+        assert_eq!(
+            sinks("eval(unescape(location.search.substr(1)))"),
+            [("location.search".to_owned(), "eval".to_owned(), Confidence::Medium)]
+        );
+    }
+
+    #[test]
+    fn follows_tainted_variables() {
+        let script = "var h = location.hash.slice(1);\nvar html = '<b>' + h;\n$('#out').html(html);";
+        assert_eq!(
+            sinks(script),
+            [("location.hash".to_owned(), "jQuery html()".to_owned(), Confidence::Low)]
+        );
+    }
+
+    #[test]
+    fn skips_sanitized_flows() {
+        assert!(sinks("el.innerHTML = encodeURIComponent(location.hash);").is_empty());
+        assert!(sinks("el.innerHTML = escape(location.hash);").is_empty());
+        assert!(sinks("var q = DOMPurify.sanitize(location.search); el.innerHTML = q;").is_empty());
+        assert!(sinks("setTimeout(function () { go(location.hash) }, 10);").is_empty());
+    }
+
+    #[test]
+    fn message_data_needs_a_listener() {
+        assert!(sinks("el.innerHTML = event.data;").is_empty());
+        let script = "window.addEventListener('message', function (event) { el.innerHTML = event.data; });";
+        assert_eq!(
+            sinks(script),
+            [("event.data".to_owned(), "innerHTML".to_owned(), Confidence::Medium)]
+        );
+    }
+}
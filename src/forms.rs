@@ -0,0 +1,242 @@
+use reqwest::Method;
+use url::Url;
+
+use crate::html::{tokenize, Token};
+use crate::request::{Body, Part, RequestTemplate};
+
+// input types that are never submitted with their form
+const UNSUBMITTED_TYPES: [&str; 4] = ["submit", "reset", "button", "image"];
+
+/// Turns every `<form>` in `html` into a request template, resolving its
+/// action against `base`.
+///
+/// Fields keep the defaults a browser would submit: input values, checked
+/// checkboxes and radio buttons, textarea contents and the selected (or
+/// first) option of each select. Empty fields get a value that passes their
+/// type's validation. Forms without fields are skipped.
+pub fn extract_forms(html: &str, base: &Url) -> Vec<RequestTemplate> {
+    let mut forms = Vec::new();
+    let mut current: Option<Form> = None;
+    // the select being read, with its first and selected option values
+    let mut select: Option<(String, Option<String>, Option<String>)> = None;
+
+    let tokens = tokenize(html);
+    for (i, token) in tokens.iter().enumerate() {
+        let next_text = || match tokens.get(i + 1) {
+            Some(Token::Text { text, .. }) => text.clone(),
+            _ => String::new(),
+        };
+
+        match token {
+            Token::StartTag { name, .. } if name == "form" => {
+                forms.extend(current.take().and_then(|form| form.into_request(base)));
+                current = Some(Form {
+                    action: token.attr("action").unwrap_or_default().trim().to_owned(),
+                    method: token.attr("method").unwrap_or("get").to_ascii_lowercase(),
+                    multipart: token.attr("enctype").is_some_and(|e| e.eq_ignore_ascii_case("multipart/form-data")),
+                    fields: Vec::new(),
+                });
+            }
+            Token::EndTag { name } if name == "form" => {
+                forms.extend(current.take().and_then(|form| form.into_request(base)));
+            }
+            _ => {}
+        }
+
+        let Some(form) = current.as_mut() else {
+            continue;
+        };
+
+        match token {
+            Token::StartTag { name, .. } if name == "input" => {
+                let Some(field) = token.attr("name").filter(|name| !name.is_empty()) else {
+                    continue;
+                };
+                let kind = token.attr("type").unwrap_or("text").to_ascii_lowercase();
+                if UNSUBMITTED_TYPES.contains(&kind.as_str()) {
+                    continue;
+                }
+                if matches!(kind.as_str(), "checkbox" | "radio") {
+                    if token.attr("checked").is_some() {
+                        form.push(field, token.attr("value").unwrap_or("on"));
+                    }
+                    continue;
+                }
+                if kind == "file" {
+                    form.fields.push(Part::file(field, "crabxss.txt"));
+                    continue;
+                }
+                let value = token.attr("value").unwrap_or_default();
+                form.push(field, &default_value(&kind, value));
+            }
+            Token::StartTag { name, .. } if name == "textarea" => {
+                if let Some(field) = token.attr("name") {
+                    form.push(field, &default_value("text", &next_text()));
+                }
+            }
+            Token::StartTag { name, .. } if name == "select" => {
+                select = token.attr("name").map(|name| (name.to_owned(), None, None));
+            }
+            Token::StartTag { name, .. } if name == "option" => {
+                if let Some((_, first, selected)) = select.as_mut() {
+                    let value = token
+                        .attr("value")
+                        .map(str::to_owned)
+                        .unwrap_or_else(|| next_text().trim().to_owned());
+                    if token.attr("selected").is_some() && selected.is_none() {
+                        *selected = Some(value.clone());
+                    }
+                    first.get_or_insert(value);
+                }
+            }
+            Token::EndTag { name } if name == "select" => {
+                if let Some((field, first, selected)) = select.take() {
+                    if let Some(value) = selected.or(first) {
+                        form.push(&field, &value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // a form left open at the end of the document is still submitted
+    forms.extend(current.and_then(|form| form.into_request(base)));
+    forms
+}
+
+struct Form {
+    action: String,
+    method: String,
+    multipart: bool,
+    fields: Vec<Part>,
+}
+
+impl Form {
+    fn push(&mut self, name: &str, value: &str) {
+        self.fields.push(Part {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            filename: None,
+        });
+    }
+
+    fn into_request(self, base: &Url) -> Option<RequestTemplate> {
+        if self.fields.is_empty() {
+            return None;
+        }
+        let mut url = base.join(&self.action).ok()?;
+        url.set_fragment(None);
+
+        if self.method != "post" {
+            // a GET submission replaces the query of the action
+            let pairs: Vec<(String, String)> = self
+                .fields
+                .into_iter()
+                .filter(|field| field.filename.is_none())
+                .map(|field| (field.name, field.value))
+                .collect();
+            url.query_pairs_mut().clear().extend_pairs(&pairs);
+            return Some(RequestTemplate::get(url));
+        }
+
+        let body = if self.multipart {
+            Body::Multipart(self.fields)
+        } else {
+            Body::Form(
+                self.fields
+                    .into_iter()
+                    .map(|field| (field.name, field.filename.unwrap_or(field.value)))
+                    .collect(),
+            )
+        };
+        Some(RequestTemplate::new(Method::POST, url, body))
+    }
+}
+
+// a value that passes the validation of an input of type `kind`, used when
+// the form leaves the field empty
+fn default_value(kind: &str, value: &str) -> String {
+    if !value.is_empty() {
+        return value.to_owned();
+    }
+    match kind {
+        "email" => "test@example.com",
+        "url" => "https://example.com",
+        "number" | "range" => "1",
+        "tel" => "5555555555",
+        "date" => "2024-01-01",
+        "hidden" => "",
+        _ => "test",
+    }
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("https://example.com/dir/page").unwrap()
+    }
+
+    #[test]
+    fn get_form_submits_browser_defaults() {
+        let html = r#"<form action="search?old=1">
+            <input type="hidden" name="tok" value="abc">
+            <input name="q">
+            <input type="email" name="mail">
+            <input type="checkbox" name="on" checked>
+            <input type="checkbox" name="off" value="1">
+            <input type="radio" name="r" value="b" checked>
+            <input type="submit" name="go" value="Go">
+            <select name="s"><option>first</option><option value="2" selected>second</option></select>
+            <select name="t"><option value="a">A</option><option value="b">B</option></select>
+            <textarea name="ta"></textarea>
+        </form>"#;
+
+        let forms = extract_forms(html, &base());
+        assert_eq!(forms.len(), 1);
+        assert_eq!(forms[0].method, Method::GET);
+        assert_eq!(
+            forms[0].url.as_str(),
+            "https://example.com/dir/search?tok=abc&q=test&mail=test%40example.com&on=on&r=b&s=2&t=a&ta=test"
+        );
+    }
+
+    #[test]
+    fn post_forms_become_bodies() {
+        let html = r#"<form method="POST" action="/save"><input name="a" value="1"><input type="number" name="n"></form>
+            <form method="post" enctype="multipart/form-data"><input name="b"><input type="file" name="f"></form>
+            <form><input type="submit" name="only"></form>"#;
+
+        let forms = extract_forms(html, &base());
+        assert_eq!(forms.len(), 2);
+
+        assert_eq!(forms[0].url.as_str(), "https://example.com/save");
+        assert_eq!(forms[0].body, Body::Form(vec![("a".into(), "1".into()), ("n".into(), "1".into())]));
+
+        assert_eq!(forms[1].url.as_str(), "https://example.com/dir/page");
+        assert_eq!(
+            forms[1].body,
+            Body::Multipart(vec![
+                Part {
+                    name: "b".into(),
+                    value: "test".into(),
+                    filename: None
+                },
+                Part::file("f", "crabxss.txt"),
+            ])
+        );
+    }
+
+    #[test]
+    fn empty_fields_get_valid_values() {
+        assert_eq!(default_value("email", ""), "test@example.com");
+        assert_eq!(default_value("url", ""), "https://example.com");
+        assert_eq!(default_value("number", ""), "1");
+        assert_eq!(default_value("hidden", ""), "");
+        assert_eq!(default_value("text", ""), "test");
+        assert_eq!(default_value("email", "me@example.org"), "me@example.org");
+    }
+}
@@ -0,0 +1,143 @@
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::states::RawKind;
+use html5ever::tokenizer::{
+    BufferQueue, TagKind, Token as RawToken, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+};
+use std::cell::RefCell;
+
+/// The tokenizer state text was read in, which decides how the browser
+/// treats markup inside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextKind {
+    /// Regular element content, where tags are parsed.
+    Data,
+    /// The body of a `<script>` element.
+    Script,
+    /// The body of `<style>` and other raw text elements.
+    Rawtext,
+    /// The body of `<textarea>` and `<title>`, where only entities are decoded.
+    Rcdata,
+}
+
+/// A simplified HTML5 token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    StartTag {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    EndTag {
+        name: String,
+    },
+    Text {
+        text: String,
+        kind: TextKind,
+    },
+    Comment(String),
+}
+
+impl Token {
+    /// The value of attribute `name` if this is a start tag that has it.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        match self {
+            Token::StartTag { attrs, .. } => attrs
+                .iter()
+                .find(|(attr, _)| attr == name)
+                .map(|(_, value)| value.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a start tag named `name`.
+    pub fn is_start_tag(&self, name: &str) -> bool {
+        matches!(self, Token::StartTag { name: tag, .. } if tag == name)
+    }
+}
+
+/// Tokenizes `html` the way a browser would, switching to the raw text
+/// states for `<script>`, `<style>`, `<textarea>` and friends.
+pub fn tokenize(html: &str) -> Vec<Token> {
+    let sink = Sink::default();
+    let tokenizer = Tokenizer::new(sink, TokenizerOpts::default());
+
+    let input = BufferQueue::default();
+    input.push_back(StrTendril::from_slice(html));
+    let _ = tokenizer.feed(&input);
+    tokenizer.end();
+
+    tokenizer.sink.tokens.into_inner()
+}
+
+#[derive(Default)]
+struct Sink {
+    tokens: RefCell<Vec<Token>>,
+    text_kind: RefCell<Option<TextKind>>,
+}
+
+impl Sink {
+    fn push_text(&self, text: &str) {
+        let kind = self.text_kind.borrow().unwrap_or(TextKind::Data);
+        let mut tokens = self.tokens.borrow_mut();
+
+        // the tokenizer emits text in chunks, so merge adjacent runs
+        if let Some(Token::Text { text: last, kind: last_kind }) = tokens.last_mut() {
+            if *last_kind == kind {
+                last.push_str(text);
+                return;
+            }
+        }
+
+        tokens.push(Token::Text {
+            text: text.to_owned(),
+            kind,
+        });
+    }
+}
+
+impl TokenSink for Sink {
+    type Handle = ();
+
+    fn process_token(&self, token: RawToken, _line_number: u64) -> TokenSinkResult<()> {
+        match token {
+            RawToken::TagToken(tag) => {
+                let name = tag.name.to_string();
+
+                if tag.kind == TagKind::EndTag {
+                    *self.text_kind.borrow_mut() = None;
+                    self.tokens.borrow_mut().push(Token::EndTag { name });
+                    return TokenSinkResult::Continue;
+                }
+
+                let attrs = tag
+                    .attrs
+                    .iter()
+                    .map(|attr| (attr.name.local.to_string(), attr.value.to_string()))
+                    .collect();
+                self.tokens.borrow_mut().push(Token::StartTag { name: name.clone(), attrs });
+
+                let (kind, raw) = match name.as_str() {
+                    "script" => (TextKind::Script, RawKind::ScriptData),
+                    "style" | "xmp" | "iframe" | "noembed" | "noframes" => (TextKind::Rawtext, RawKind::Rawtext),
+                    "textarea" | "title" => (TextKind::Rcdata, RawKind::Rcdata),
+                    "plaintext" => {
+                        *self.text_kind.borrow_mut() = Some(TextKind::Rawtext);
+                        return TokenSinkResult::Plaintext;
+                    }
+                    _ => return TokenSinkResult::Continue,
+                };
+
+                *self.text_kind.borrow_mut() = Some(kind);
+                TokenSinkResult::RawData(raw)
+            }
+            RawToken::CharacterTokens(text) => {
+                self.push_text(&text);
+                TokenSinkResult::Continue
+            }
+            RawToken::CommentToken(text) => {
+                self.tokens.borrow_mut().push(Token::Comment(text.to_string()));
+                TokenSinkResult::Continue
+            }
+            _ => TokenSinkResult::Continue,
+        }
+    }
+}
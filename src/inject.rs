@@ -0,0 +1,343 @@
+use percent_encoding::percent_decode_str;
+use serde_json::Value;
+use std::fmt;
+use url::{form_urlencoded, Url};
+
+use crate::cookies::{encode_cookie_value, format_cookie_header, parse_cookie_header};
+use crate::request::{Body, RequestTemplate};
+
+/// The headers injected by default in header injection mode.
+pub const DEFAULT_INJECTED_HEADERS: [&str; 4] = ["Referer", "User-Agent", "X-Forwarded-For", "X-Forwarded-Host"];
+
+/// A place in a request where a payload can be injected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InjectionPoint {
+    /// The value of the `index`th query parameter.
+    Query { index: usize, name: String },
+    /// The value of the `index`th parameter of a form body.
+    Body { index: usize, name: String },
+    /// A string in a JSON body, addressed by its JSON pointer.
+    Json { pointer: String },
+    /// The value of the `index`th field of a multipart body.
+    MultipartField { index: usize, name: String },
+    /// The file name of the `index`th field of a multipart body.
+    Filename { index: usize, name: String },
+    /// The value of a request header.
+    Header { name: String },
+    /// The value of the `index`th cookie of the `Cookie` header.
+    Cookie { index: usize, name: String },
+    /// The `index`th segment of the URL path, whose original value is `name`.
+    PathSegment { index: usize, name: String },
+    /// An extra segment appended to the URL path.
+    AppendedSegment,
+}
+
+impl InjectionPoint {
+    /// The name of the injected parameter.
+    pub fn name(&self) -> &str {
+        match self {
+            InjectionPoint::Query { name, .. }
+            | InjectionPoint::Body { name, .. }
+            | InjectionPoint::MultipartField { name, .. }
+            | InjectionPoint::Filename { name, .. }
+            | InjectionPoint::Header { name }
+            | InjectionPoint::Cookie { name, .. }
+            | InjectionPoint::PathSegment { name, .. } => name,
+            InjectionPoint::AppendedSegment => "",
+            InjectionPoint::Json { pointer } => pointer,
+        }
+    }
+
+    /// The value this point has in `request`.
+    pub fn value(&self, request: &RequestTemplate) -> Option<String> {
+        match self {
+            InjectionPoint::Query { index, .. } => request.url.query_pairs().nth(*index).map(|(_, v)| v.into_owned()),
+            InjectionPoint::Body { index, .. } => match &request.body {
+                Body::Form(pairs) => pairs.get(*index).map(|(_, v)| v.clone()),
+                _ => None,
+            },
+            InjectionPoint::Json { pointer } => match &request.body {
+                Body::Json(json) => json.pointer(pointer).and_then(Value::as_str).map(str::to_owned),
+                _ => None,
+            },
+            InjectionPoint::MultipartField { index, .. } => match &request.body {
+                Body::Multipart(parts) => parts.get(*index).map(|part| part.value.clone()),
+                _ => None,
+            },
+            InjectionPoint::Filename { index, .. } => match &request.body {
+                Body::Multipart(parts) => parts.get(*index).and_then(|part| part.filename.clone()),
+                _ => None,
+            },
+            InjectionPoint::Header { name } => request.header(name).map(str::to_owned),
+            InjectionPoint::Cookie { index, .. } => request
+                .header("Cookie")
+                .and_then(|header| parse_cookie_header(header).into_iter().nth(*index))
+                .map(|(_, value)| value),
+            InjectionPoint::PathSegment { index, .. } => request
+                .url
+                .path_segments()
+                .and_then(|mut segments| segments.nth(*index))
+                .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned()),
+            InjectionPoint::AppendedSegment => None,
+        }
+    }
+
+    /// Returns a copy of `request` with this point's value replaced by
+    /// `value`. Every other parameter keeps its original value.
+    pub fn apply(&self, request: &RequestTemplate, value: &str) -> RequestTemplate {
+        let mut injected = request.clone();
+
+        match self {
+            InjectionPoint::Query { index, .. } => {
+                let query = replace_query_value(request.url.query().unwrap_or_default(), *index, value);
+                injected.url.set_query(Some(&query));
+            }
+            InjectionPoint::Body { index, .. } => {
+                if let Body::Form(pairs) = &mut injected.body {
+                    if let Some((_, v)) = pairs.get_mut(*index) {
+                        *v = value.to_owned();
+                    }
+                }
+            }
+            InjectionPoint::Json { pointer } => {
+                if let Body::Json(json) = &mut injected.body {
+                    if let Some(leaf) = json.pointer_mut(pointer) {
+                        *leaf = Value::String(value.to_owned());
+                    }
+                }
+            }
+            InjectionPoint::MultipartField { index, .. } => {
+                if let Body::Multipart(parts) = &mut injected.body {
+                    if let Some(part) = parts.get_mut(*index) {
+                        part.value = value.to_owned();
+                    }
+                }
+            }
+            InjectionPoint::Filename { index, .. } => {
+                if let Body::Multipart(parts) = &mut injected.body {
+                    if let Some(part) = parts.get_mut(*index) {
+                        part.filename = Some(value.to_owned());
+                    }
+                }
+            }
+            InjectionPoint::Header { name } => injected.set_header(name, value),
+            InjectionPoint::Cookie { index, .. } => {
+                let mut cookies = parse_cookie_header(request.header("Cookie").unwrap_or_default());
+                if let Some((_, v)) = cookies.get_mut(*index) {
+                    *v = encode_cookie_value(value);
+                }
+                injected.set_header("Cookie", format_cookie_header(&cookies));
+            }
+            InjectionPoint::PathSegment { index, .. } => {
+                let mut segments: Vec<String> = path_segments(&request.url);
+                if let Some(segment) = segments.get_mut(*index) {
+                    *segment = encode_path_segment(&request.url, value);
+                }
+                injected.url.set_path(&format!("/{}", segments.join("/")));
+            }
+            InjectionPoint::AppendedSegment => {
+                let mut segments: Vec<String> = path_segments(&request.url);
+                if segments.last().is_some_and(|segment| segment.is_empty()) {
+                    segments.pop();
+                }
+                segments.push(encode_path_segment(&request.url, value));
+                injected.url.set_path(&format!("/{}", segments.join("/")));
+            }
+        }
+
+        injected
+    }
+}
+
+impl fmt::Display for InjectionPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InjectionPoint::Query { name, .. } => write!(f, "query parameter '{}'", name),
+            InjectionPoint::Body { name, .. } => write!(f, "body parameter '{}'", name),
+            InjectionPoint::Json { pointer } => write!(f, "JSON field '{}'", pointer),
+            InjectionPoint::MultipartField { name, .. } => write!(f, "multipart field '{}'", name),
+            InjectionPoint::Filename { name, .. } => write!(f, "file name of multipart field '{}'", name),
+            InjectionPoint::Header { name } => write!(f, "header '{}'", name),
+            InjectionPoint::Cookie { name, .. } => write!(f, "cookie '{}'", name),
+            InjectionPoint::PathSegment { index, name } => write!(f, "path segment {} ('{}')", index + 1, name),
+            InjectionPoint::AppendedSegment => write!(f, "appended path segment"),
+        }
+    }
+}
+
+/// Lists every cookie of `request` as an injection point.
+pub fn cookie_points(request: &RequestTemplate) -> Vec<InjectionPoint> {
+    parse_cookie_header(request.header("Cookie").unwrap_or_default())
+        .into_iter()
+        .enumerate()
+        .map(|(index, (name, _))| InjectionPoint::Cookie { index, name })
+        .collect()
+}
+
+/// Lists every segment of the URL path of `request`, plus an extra
+/// appended segment, as injection points.
+pub fn path_points(request: &RequestTemplate) -> Vec<InjectionPoint> {
+    let Some(segments) = request.url.path_segments() else {
+        return Vec::new();
+    };
+
+    let mut points: Vec<InjectionPoint> = segments
+        .enumerate()
+        .filter(|(_, segment)| !segment.is_empty())
+        .map(|(index, segment)| InjectionPoint::PathSegment {
+            index,
+            name: percent_decode_str(segment).decode_utf8_lossy().into_owned(),
+        })
+        .collect();
+    points.push(InjectionPoint::AppendedSegment);
+    points
+}
+
+// `query` with the value of its `index`th pair replaced by `value`, leaving
+// every other byte as it was so the other parameters aren't re-encoded.
+// Pairs are counted the way `Url::query_pairs` counts them, skipping empty
+// ones.
+fn replace_query_value(query: &str, index: usize, value: &str) -> String {
+    let encoded: String = form_urlencoded::byte_serialize(value.as_bytes()).collect();
+    let mut start = 0;
+    let mut seen = 0;
+
+    for pair in query.split('&') {
+        if !pair.is_empty() {
+            if seen == index {
+                let name = pair.split_once('=').map_or(pair, |(name, _)| name);
+                return format!("{}{}={}{}", &query[..start], name, encoded, &query[start + pair.len()..]);
+            }
+            seen += 1;
+        }
+        start += pair.len() + 1;
+    }
+
+    query.to_owned()
+}
+
+// the raw, still percent-encoded segments of the path of `url`
+fn path_segments(url: &Url) -> Vec<String> {
+    url.path_segments()
+        .map(|segments| segments.map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+// percent-encodes `value` as a single path segment of `url`, so that slashes,
+// backslashes and percent signs in payloads survive URL normalization
+fn encode_path_segment(url: &Url, value: &str) -> String {
+    let mut scratch = url.clone();
+    scratch.set_query(None);
+    scratch.set_fragment(None);
+    scratch.set_path("");
+    if let Ok(mut segments) = scratch.path_segments_mut() {
+        segments.clear().push(value);
+    }
+    scratch.path().trim_start_matches('/').to_owned()
+}
+
+/// Lists every injection point of `request`.
+pub fn injection_points(request: &RequestTemplate) -> Vec<InjectionPoint> {
+    let mut points: Vec<InjectionPoint> = request
+        .url
+        .query_pairs()
+        .enumerate()
+        .map(|(index, (name, _))| InjectionPoint::Query {
+            index,
+            name: name.into_owned(),
+        })
+        .collect();
+
+    match &request.body {
+        Body::Form(pairs) => {
+            points.extend(pairs.iter().enumerate().map(|(index, (name, _))| InjectionPoint::Body {
+                index,
+                name: name.clone(),
+            }));
+        }
+        Body::Json(json) => json_strings(json, String::new(), &mut points),
+        Body::Multipart(parts) => {
+            for (index, part) in parts.iter().enumerate() {
+                let name = part.name.clone();
+                if part.filename.is_some() {
+                    points.push(InjectionPoint::Filename { index, name });
+                } else {
+                    points.push(InjectionPoint::MultipartField { index, name });
+                }
+            }
+        }
+        Body::Empty => {}
+    }
+
+    points
+}
+
+// collects every string leaf of `value` as an injection point
+fn json_strings(value: &Value, pointer: String, points: &mut Vec<InjectionPoint>) {
+    match value {
+        Value::String(_) => points.push(InjectionPoint::Json { pointer }),
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                json_strings(item, format!("{}/{}", pointer, i), points);
+            }
+        }
+        Value::Object(fields) => {
+            for (key, item) in fields {
+                // escape the key as RFC 6901 requires
+                let key = key.replace('~', "~0").replace('/', "~1");
+                json_strings(item, format!("{}/{}", pointer, key), points);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_injection_leaves_other_pairs_untouched() {
+        let request = RequestTemplate::get(Url::parse("https://example.com/?a=1&flag&b=x%20y").unwrap());
+        let point = InjectionPoint::Query {
+            index: 2,
+            name: "b".to_owned(),
+        };
+        assert_eq!(point.apply(&request, "<x>").url.query(), Some("a=1&flag&b=%3Cx%3E"));
+
+        let point = InjectionPoint::Query {
+            index: 1,
+            name: "flag".to_owned(),
+        };
+        assert_eq!(point.apply(&request, "a b").url.query(), Some("a=1&flag=a+b&b=x%20y"));
+    }
+
+    #[test]
+    fn query_injection_counts_pairs_like_query_pairs() {
+        let request = RequestTemplate::get(Url::parse("https://example.com/?&a=1&&b=2").unwrap());
+        let point = InjectionPoint::Query {
+            index: 1,
+            name: "b".to_owned(),
+        };
+        assert_eq!(point.value(&request).as_deref(), Some("2"));
+        assert_eq!(point.apply(&request, "3").url.query(), Some("&a=1&&b=3"));
+    }
+
+    #[test]
+    fn json_pointers_escape_keys() {
+        let json = serde_json::json!({ "a/b": { "c~d": "x" }, "list": ["y", 1] });
+        let request = RequestTemplate::new(
+            reqwest::Method::POST,
+            Url::parse("https://example.com/").unwrap(),
+            Body::Json(json),
+        );
+
+        let points = injection_points(&request);
+        let pointers: Vec<&str> = points.iter().map(InjectionPoint::name).collect();
+        assert_eq!(pointers, ["/a~1b/c~0d", "/list/0"]);
+
+        let injected = points[0].apply(&request, "<x>");
+        assert_eq!(points[0].value(&injected).as_deref(), Some("<x>"));
+        assert_eq!(points[1].value(&injected).as_deref(), Some("y"));
+    }
+}
@@ -0,0 +1,176 @@
+/// What the JavaScript lexer is reading at a given position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsState {
+    Code,
+    /// Inside a string literal delimited by the given quote.
+    String(char),
+    Comment,
+}
+
+/// Lexes `source` up to byte offset `pos` and returns what `pos` is part of.
+///
+/// This is a best-effort lexer: it follows strings, template literals and
+/// comments, but treats regular expression literals as code.
+pub fn state_at(source: &str, pos: usize) -> JsState {
+    let mut state = JsState::Code;
+    lex(source, |step| {
+        if step.start >= pos {
+            return false;
+        }
+        state = step.after;
+        true
+    });
+    state
+}
+
+/// The statement of `source` that byte offset `pos` is part of: the code
+/// between the `;`, `{` or `}` around it, skipping the ones in strings and
+/// comments.
+pub fn statement_at(source: &str, pos: usize) -> &str {
+    let mut start = 0;
+    let mut end = source.len();
+
+    lex(source, |step| {
+        let boundary = step.before == JsState::Code && step.after == JsState::Code && matches!(step.c, ';' | '{' | '}');
+        if step.start < pos {
+            if boundary {
+                start = step.end;
+            }
+            true
+        } else if boundary && step.c != '{' {
+            end = step.start;
+            false
+        } else {
+            true
+        }
+    });
+
+    &source[start..end.max(start)]
+}
+
+// one character read by `lex`, with the state before and after it
+struct Step {
+    start: usize,
+    // past the character and any the lexer consumed with it
+    end: usize,
+    c: char,
+    before: JsState,
+    after: JsState,
+}
+
+// lexes `source`, passing each character to `visit` until it returns false
+fn lex(source: &str, mut visit: impl FnMut(Step) -> bool) {
+    let mut state = JsState::Code;
+    // brace depth of every `${` we are inside of
+    let mut templates: Vec<usize> = Vec::new();
+    let mut depth = 0;
+    let mut block_comment = false;
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let before = state;
+
+        match state {
+            JsState::Code => match c {
+                '"' | '\'' | '`' => state = JsState::String(c),
+                '/' => match chars.peek() {
+                    Some((_, '/')) => {
+                        chars.next();
+                        state = JsState::Comment;
+                        block_comment = false;
+                    }
+                    Some((_, '*')) => {
+                        chars.next();
+                        state = JsState::Comment;
+                        block_comment = true;
+                    }
+                    _ => {}
+                },
+                '{' => depth += 1,
+                '}' => {
+                    if templates.last() == Some(&depth) {
+                        templates.pop();
+                        state = JsState::String('`');
+                    } else {
+                        depth = depth.saturating_sub(1);
+                    }
+                }
+                _ => {}
+            },
+            JsState::String(quote) => match c {
+                '\\' => {
+                    chars.next();
+                }
+                '$' if quote == '`' && matches!(chars.peek(), Some((_, '{'))) => {
+                    chars.next();
+                    templates.push(depth);
+                    state = JsState::Code;
+                }
+                '\n' if quote != '`' => state = JsState::Code,
+                _ if c == quote => state = JsState::Code,
+                _ => {}
+            },
+            JsState::Comment => {
+                if block_comment {
+                    if c == '*' && matches!(chars.peek(), Some((_, '/'))) {
+                        chars.next();
+                        state = JsState::Code;
+                    }
+                } else if c == '\n' {
+                    state = JsState::Code;
+                }
+            }
+        }
+
+        let end = chars.peek().map_or(source.len(), |(next, _)| *next);
+        let step = Step {
+            start: i,
+            end,
+            c,
+            before,
+            after: state,
+        };
+        if !visit(step) {
+            break;
+        }
+    }
+}
+
+/// Whether the `code` part of an injected `payload` ends up as executable
+/// code in `source` rather than inside a string or comment.
+pub fn runs_as_code(source: &str, payload: &str, code: &str) -> bool {
+    let Some(offset) = payload.find(code) else {
+        return false;
+    };
+
+    source
+        .match_indices(payload)
+        .any(|(pos, _)| state_at(source, pos + offset) == JsState::Code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_strings_comments_and_templates() {
+        let source = r#"var a = "x\"y", b = `t${c + '}'}u`; // z"#;
+        assert_eq!(state_at(source, source.find('y').unwrap()), JsState::String('"'));
+        assert_eq!(state_at(source, source.find("c +").unwrap()), JsState::Code);
+        assert_eq!(state_at(source, source.find("'}'").unwrap() + 1), JsState::String('\''));
+        assert_eq!(state_at(source, source.find('u').unwrap()), JsState::String('`'));
+        assert_eq!(state_at(source, source.find('z').unwrap()), JsState::Comment);
+    }
+
+    #[test]
+    fn extracts_the_enclosing_statement() {
+        let source = "function f() { var x = '';alert(1);//'; g(); }";
+        assert_eq!(statement_at(source, source.find("alert").unwrap()), "alert(1)");
+
+        let source = "var a = 1; var s = 'a;b' + ''-alert(1)-''; next();";
+        assert_eq!(statement_at(source, source.find("alert").unwrap()), " var s = 'a;b' + ''-alert(1)-''");
+
+        let source = "x = `${alert(1)}`; y = 2";
+        assert_eq!(statement_at(source, source.find("alert").unwrap()), "x = `${alert(1)}`");
+    }
+}
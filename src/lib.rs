@@ -0,0 +1,41 @@
+use error_chain::error_chain;
+
+pub mod confirm;
+pub mod cookies;
+pub mod crawl;
+pub mod dom;
+pub mod forms;
+pub mod html;
+pub mod inject;
+pub mod js;
+pub mod mine;
+pub mod output;
+pub mod payloads;
+pub mod probe;
+pub mod reflection;
+pub mod report;
+pub mod request;
+pub mod result;
+pub mod scanner;
+pub mod throttle;
+
+pub use request::{Body, Part, RequestTemplate};
+pub use result::{Confidence, Finding, ScanResult, Skipped, StoredFinding};
+pub use scanner::{parse_header, Scanner, ScannerBuilder};
+
+error_chain! {
+    errors {
+        /// A host kept throttling or blocking a request after every retry.
+        Throttled(host: String, reason: String) {
+            description("host kept pushing back")
+            display("{} kept pushing back after {} retries: {}", host, throttle::MAX_RETRIES, reason)
+        }
+    }
+
+    foreign_links {
+        Io(std::io::Error);
+        HttpRequest(reqwest::Error);
+        UrlParse(url::ParseError);
+        RegexError(regex::Error);
+    }
+}
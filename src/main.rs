@@ -1,21 +1,18 @@
-use error_chain::error_chain;
-use futures::stream::{self, StreamExt};
-use std::io::{self, BufRead};
-use std::fs::File;
-use std::path::PathBuf;
-use url::Url;
-use regex::Regex;
-use urlencoding::decode;
 use clap::Parser;
-
-error_chain! {
-    foreign_links {
-        Io(std::io::Error);
-        HttpRequest(reqwest::Error);
-        UrlParse(url::ParseError);
-        RegexError(regex::Error);
-    }
-}
+use crabxss::cookies::parse_cookie_file;
+use crabxss::inject::DEFAULT_INJECTED_HEADERS;
+use crabxss::mine::wordlist;
+use crabxss::output::{jsonl, OutputFormat, SarifWriter};
+use crabxss::report::HtmlReport;
+use crabxss::{Body, Error, Part, Result, Scanner};
+use reqwest::Method;
+use futures::stream::{self, Stream, StreamExt};
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
 
 #[derive(Parser, Debug)]
 #[clap(author = "by wintermut3", version = "1.3", about = None, long_about = None)]
@@ -26,146 +23,226 @@ struct Args {
     #[clap(short = 'l', long = "list", value_name = "FILE", help = "File containing URLs (one per line)")]
     url_list: Option<PathBuf>,
 
+    #[clap(short = 'X', long = "method", value_name = "METHOD", help = "HTTP method to use (defaults to POST when a body is set, GET otherwise)")]
+    method: Option<String>,
+
+    #[clap(short = 'd', long = "data", value_name = "DATA", help = "Form body template (e.g. 'user=foo&q=bar'); its parameters are injected too")]
+    data: Option<String>,
+
+    #[clap(long = "json", value_name = "JSON", conflicts_with = "data", help = "JSON body template; every string in it is injected too")]
+    json: Option<String>,
+
+    #[clap(short = 'F', long = "form", value_name = "FIELD", conflicts_with_all = &["data", "json"], help = "Multipart field template: 'name=value', or 'name=@filename' for a file field whose file name is injected")]
+    form: Vec<String>,
+
+    #[clap(long = "inject-headers", value_name = "HEADER", min_values = 0, use_value_delimiter = true, help = "Also inject into these request headers, one at a time (defaults to Referer, User-Agent, X-Forwarded-For and X-Forwarded-Host)")]
+    inject_headers: Option<Vec<String>>,
+
+    #[clap(long = "inject-path", help = "Also inject into each URL path segment and an appended extra segment")]
+    inject_path: bool,
+
+    #[clap(long = "cookie-file", value_name = "FILE", help = "Cookie file (Netscape format or name=value lines) whose cookies are sent to the hosts and paths they belong to")]
+    cookie_file: Option<PathBuf>,
+
+    #[clap(long = "inject-cookies", help = "Also inject into each cookie in turn, from -H 'Cookie: ...' and --cookie-file")]
+    inject_cookies: bool,
+
+    #[clap(short = 'p', long = "payloads", value_name = "FILE", help = "File containing payloads to inject into every parameter (one per line)")]
+    payload_list: Option<PathBuf>,
+
+    #[clap(long = "passive", help = "Only check whether the values already in each URL are reflected (e.g. with qsreplace)")]
+    passive: bool,
+
+    #[clap(long = "dom", help = "Also trace DOM XSS sources to sinks in the inline and same-host scripts of each page")]
+    dom: bool,
+
+    #[clap(long = "observe", value_name = "URL", help = "Look for stored XSS: inject tagged payloads, then check this page for them (can be repeated)")]
+    observe: Vec<String>,
+
+    #[clap(long = "observe-delay", value_name = "SECONDS", help = "Seconds to wait before checking the observation pages a second time", default_value = "10")]
+    observe_delay: u64,
+
+    #[clap(long = "mine", help = "Mine hidden parameters from a wordlist and scan the ones that are reflected or change the response")]
+    mine: bool,
+
+    #[clap(long = "wordlist", value_name = "FILE", help = "Parameter names to mine for (one per line) instead of the built-in list")]
+    wordlist: Option<PathBuf>,
+
+    #[clap(long = "forms", help = "Also scan every form on the given pages, submitting each field with the others at their defaults")]
+    forms: bool,
+
+    #[clap(long = "crawl", help = "Crawl from the given URLs and scan the parameterized endpoints and forms found on the same hosts")]
+    crawl: bool,
+
+    #[clap(long = "depth", value_name = "DEPTH", help = "How many links deep to crawl", default_value = "2")]
+    depth: usize,
+
+    #[clap(long = "output-format", value_name = "FORMAT", possible_values = &["text", "jsonl", "sarif"], default_value = "text", help = "Output format: text, jsonl for one JSON object per finding, or sarif for a SARIF 2.1.0 log")]
+    output_format: String,
+
+    #[clap(long = "html-report", value_name = "FILE", help = "Also write a self-contained HTML report of the findings to this file")]
+    html_report: Option<PathBuf>,
+
     #[clap(short = 't', long = "threads", value_name = "THREADS", help = "Number of concurrent threads", default_value = "5")]
     threads: usize,
+
+    #[clap(long = "rate", value_name = "RATE", help = "Maximum number of requests per second across all hosts")]
+    rate: Option<f64>,
+
+    #[clap(long = "host-rate", value_name = "RATE", help = "Maximum number of requests per second to any single host")]
+    host_rate: Option<f64>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
-    // read URLs from file
+    let output_format: OutputFormat = args.output_format.parse()?;
+
+    // read URLs from file, as the scan goes so huge lists aren't held in memory
     let urls = if let Some(file_path) = args.url_list {
-        let file = File::open(file_path)?;
-        io::BufReader::new(file)
-            .lines()
-            .filter_map(|line| line.ok())
-            .map(|line| line.trim().to_owned())
-            .filter(|line| !line.is_empty())
-            .collect()
+        let file = File::open(file_path).await?;
+        read_lines(BufReader::new(file)).boxed_local()
     } else {
         // read URLs from stdin
-        io::stdin()
-            .lock()
-            .lines()
-            .filter_map(|line| line.ok())
-            .map(|line| line.trim().to_owned())
-            .filter(|line| !line.is_empty())
-            .collect()
+        read_lines(BufReader::new(tokio::io::stdin())).boxed_local()
     };
+    let mut urls = urls.peekable();
 
-    let urls: Vec<String> = urls;
-    let total_urls = urls.len();
+    // read payloads from file
+    let payloads: Vec<String> = match args.payload_list {
+        Some(file_path) => read_lines(BufReader::new(File::open(file_path).await?)).collect().await,
+        None => Vec::new(),
+    };
 
-    if urls.is_empty() {
+    if Pin::new(&mut urls).peek().await.is_none() {
         println!("No URLs provided. Please either pipe URLs to the program or use the -l option to specify a file.");
         return Ok(());
     }
 
-    println!("Starting scan with {} threads for {} URLs", args.threads, total_urls);
-
-    let client = reqwest::Client::new();
-
-    let results = stream::iter(urls)
-        .map(|url| {
-            let client = client.clone();
-            let headers = args.headers.clone();
-            tokio::spawn(async move {
-                match check_xss_reflection(&client, &url, &headers).await {
-                    Ok(result) => result,
-                    Err(e) => (url, format!("Error: {:?}", e)),
-                }
-            })
-        })
-        .buffer_unordered(args.threads)
-        .collect::<Vec<_>>()
-        .await;
-
-    for result in results {
-        match result {
-            Ok((url, status)) => println!("{} -> {}", url, status),
-            Err(e) => eprintln!("Task error: {:?}", e),
-        }
+    let mut builder = args
+        .headers
+        .iter()
+        .fold(Scanner::builder(), |builder, header| builder.raw_header(header));
+
+    if let Some(method) = &args.method {
+        let method = Method::from_bytes(method.to_uppercase().as_bytes())
+            .map_err(|_| Error::from(format!("Invalid HTTP method: {}", method)))?;
+        builder = builder.method(method);
     }
 
-    Ok(())
-}
+    if let Some(data) = &args.data {
+        builder = builder.body(Body::form(data));
+    }
 
-async fn check_xss_reflection(client: &reqwest::Client, url: &str, custom_headers: &[String]) -> Result<(String, String)> {
-    let mut request = client.get(url);
-    
-    for header in custom_headers {
-        let parts: Vec<&str> = header.splitn(2, ':').collect();
-        if parts.len() == 2 {
-            request = request.header(parts[0].trim(), parts[1].trim());
-        }
+    if let Some(json) = &args.json {
+        builder = builder.body(Body::json(json)?);
     }
 
-    let resp = request.send().await?;
-    let status = resp.status();
-    let body = resp.text().await?;
-
-    let parsed_url = Url::parse(url)?;
-    let query_params: Vec<(String, String)> = parsed_url
-        .query_pairs()
-        .map(|(k, v)| (k.into_owned(), v.into_owned()))
-        .collect();
-
-    for (_, value) in query_params {
-        let decoded_value = decode(&value).map_err(|e| Error::from(format!("Decoding error: {}", e)))?;
-        if let Some(injected_tags) = extract_tags_from_param(&decoded_value) {
-            for tag in injected_tags {
-                if body.contains(&tag) {
-                    return Ok((
-                        url.to_string(),
-                        format!(
-                            "Potential XSS found! Tag '{}' reflected ({})",
-                            tag, status
-                        ),
-                    ));
-                }
-            }
+    if !args.form.is_empty() {
+        let parts = args
+            .form
+            .iter()
+            .map(|field| Part::parse(field).ok_or_else(|| Error::from(format!("Invalid multipart field: {}", field))))
+            .collect::<Result<Vec<_>>>()?;
+        builder = builder.body(Body::Multipart(parts));
+    }
+
+    if let Some(names) = args.inject_headers {
+        builder = if names.is_empty() {
+            builder.inject_headers(DEFAULT_INJECTED_HEADERS)
+        } else {
+            builder.inject_headers(names)
+        };
+    }
+
+    if let Some(file_path) = &args.wordlist {
+        builder = builder.wordlist(wordlist(&std::fs::read_to_string(file_path)?));
+    }
+
+    if let Some(file_path) = &args.cookie_file {
+        let contents = std::fs::read_to_string(file_path)?;
+        builder = parse_cookie_file(&contents)
+            .into_iter()
+            .fold(builder, |builder, cookie| builder.add_cookie(cookie));
+    }
+
+    if let Some(rate) = args.rate {
+        builder = builder.rate(rate);
+    }
+
+    if let Some(rate) = args.host_rate {
+        builder = builder.host_rate(rate);
+    }
+
+    let scanner = builder
+        .inject_path(args.inject_path)
+        .inject_cookies(args.inject_cookies)
+        .payloads(payloads)
+        .passive(args.passive)
+        .dom(args.dom)
+        .mine(args.mine)
+        .forms(args.forms && !args.crawl)
+        .observe(args.observe)
+        .observe_delay(Duration::from_secs(args.observe_delay))
+        .concurrency(args.threads)
+        .build()?;
+
+    // results are written as they come in rather than once the scan is done
+    let mut results = if args.crawl {
+        let seeds: Vec<String> = urls.collect().await;
+        let requests = scanner.crawl(seeds.iter().cloned(), args.depth).await;
+        eprintln!("Crawled {} seed URLs and found {} endpoints and forms", seeds.len(), requests.len());
+        eprintln!("Starting scan with {} threads for {} requests", args.threads, requests.len());
+        scanner.scan_requests(requests).boxed_local()
+    } else {
+        eprintln!("Starting scan with {} threads", args.threads);
+        scanner.scan_stream(urls).boxed_local()
+    };
+
+    let mut sarif = match output_format {
+        OutputFormat::Sarif => Some(SarifWriter::new(io::stdout())?),
+        _ => None,
+    };
+    let mut report = args.html_report.as_ref().map(|_| HtmlReport::default());
+
+    while let Some(result) = results.next().await {
+        match output_format {
+            OutputFormat::Text => println!("{}", result),
+            OutputFormat::Jsonl => println!("{}", jsonl(&result)),
+            OutputFormat::Sarif => {}
         }
+        if let Some(writer) = &mut sarif {
+            writer.write(&result)?;
+        }
+        if let Some(report) = &mut report {
+            report.add(result);
+        }
+    }
+
+    if let Some(writer) = sarif {
+        writer.finish()?;
     }
 
-    Ok((
-        url.to_string(),
-        format!("No tag reflection found ({})", status),
-    ))
+    if let (Some(file_path), Some(report)) = (&args.html_report, &report) {
+        std::fs::write(file_path, report.render())?;
+    }
+
+    Ok(())
 }
 
-fn extract_tags_from_param(param: &str) -> Option<Vec<String>> {
-    // regex patterns for different types of tags
-    let patterns = vec![
-        // tags with closing
-        r"<[^>]+>[^<]*</[^>]+>",
-        // tags self-closing or without closing
-        r"<[^>]+>",
-        // specifics attributes that mat indicate XSS
-        r"onerror=[^>\s]+",
-        r"OnError=[^>\s]+",
-        r"onclick=[^>\s]+",
-        r"OnCliCk=[^>\s]+",
-        r"onload=[^>\s]+",
-        r"OnLoAd=[^>\s]+",
-        r"ontoggle=[^>\s]+",
-        r"OnToGgLe=[^>\s]+",
-        r"src=[^>\s]+",
-    ];
-
-    let mut found_tags = Vec::new();
-
-    for pattern in patterns {
-        if let Ok(re) = Regex::new(pattern) {
-            for cap in re.find_iter(param) {
-                found_tags.push(cap.as_str().to_string());
+// the trimmed, non-empty lines of `reader`, stopping at the end or at the
+// first I/O error; invalid UTF-8 is decoded lossily so one bad line doesn't
+// drop the ones after it
+fn read_lines(reader: impl AsyncBufRead + Unpin) -> impl Stream<Item = String> {
+    stream::unfold(reader.split(b'\n'), |mut lines| async move {
+        while let Ok(Some(line)) = lines.next_segment().await {
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if !line.is_empty() {
+                return Some((line.to_owned(), lines));
             }
         }
-    }
-
-    if found_tags.is_empty() {
         None
-    } else {
-        Some(found_tags)
-    }
-}
\ No newline at end of file
+    })
+}
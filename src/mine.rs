@@ -0,0 +1,247 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+use crate::html::{tokenize, TextKind, Token};
+use crate::probe::canary;
+use crate::request::RequestTemplate;
+
+// names longer than this are unlikely to be parameters
+const MAX_NAME_LEN: usize = 40;
+
+/// The built-in list of common parameter names, used when no wordlist is
+/// given.
+pub const DEFAULT_WORDLIST: &str = include_str!("../wordlists/params.txt");
+
+/// How many candidate names are sent in a single request.
+pub const BATCH_SIZE: usize = 128;
+
+/// The names in a wordlist, one per line, skipping blank lines and
+/// `#` comments.
+pub fn wordlist(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// A copy of `request` with `params` appended to its query string.
+pub fn with_params<'a>(request: &RequestTemplate, params: impl IntoIterator<Item = (&'a str, &'a str)>) -> RequestTemplate {
+    let mut request = request.clone();
+    request.url.query_pairs_mut().extend_pairs(params);
+    request
+}
+
+/// Narrows a batch of candidate names down to the ones an endpoint reads.
+///
+/// Each name is sent with its own canary. Names whose canary comes back are
+/// found outright, and a set of names that changes the response is split in
+/// half until single names are left.
+pub struct Bisection {
+    pending: Vec<Vec<(String, String)>>,
+    found: Vec<String>,
+}
+
+impl Bisection {
+    pub fn new(names: &[String]) -> Self {
+        Bisection {
+            pending: vec![names.iter().map(|name| (name.clone(), canary())).collect()],
+            found: Vec::new(),
+        }
+    }
+
+    /// The next names to send, each with its canary.
+    pub fn next_batch(&mut self) -> Option<Vec<(String, String)>> {
+        self.pending.pop()
+    }
+
+    /// Records the response to `params`: the names whose canary is in
+    /// `body` are found, and when the response `changed` from the baseline
+    /// the rest is split in half to be sent again.
+    pub fn record(&mut self, params: Vec<(String, String)>, body: &str, changed: bool) {
+        for (name, value) in &params {
+            if body.contains(value.as_str()) && !self.found.contains(name) {
+                self.found.push(name.clone());
+            }
+        }
+
+        if !changed {
+            return;
+        }
+        if let [(name, _)] = params.as_slice() {
+            if !self.found.contains(name) {
+                self.found.push(name.clone());
+            }
+            return;
+        }
+
+        let rest: Vec<(String, String)> = params.into_iter().filter(|(name, _)| !self.found.contains(name)).collect();
+        let (left, right) = rest.split_at(rest.len().div_ceil(2));
+        for half in [right, left] {
+            if !half.is_empty() {
+                self.pending.push(half.to_vec());
+            }
+        }
+    }
+
+    /// The names found so far, in the order they were found.
+    pub fn found(self) -> Vec<String> {
+        self.found
+    }
+}
+
+/// `body` without the echoes of the canaries in `params`, along with their
+/// names and separators, so a page that prints its own URL doesn't look
+/// like it changed.
+pub fn strip_params(body: &str, params: &[(String, String)]) -> String {
+    if params.is_empty() {
+        return body.to_owned();
+    }
+    let canaries: Vec<String> = params.iter().map(|(_, canary)| regex::escape(canary)).collect();
+    let pattern = format!(r"(?:&amp;|[&?;])?(?:[\w.\-\[\]%]+=)?(?:{})", canaries.join("|"));
+
+    match Regex::new(&pattern) {
+        Ok(re) => re.replace_all(body, "").into_owned(),
+        Err(_) => body.to_owned(),
+    }
+}
+
+/// Candidate parameter names taken from a page: the `name` and `id` of its
+/// form controls, and, in its scripts, variable names, object keys and the
+/// query keys in string literals.
+pub fn page_params(html: &str) -> Vec<String> {
+    let re = patterns();
+    let mut names: Vec<String> = Vec::new();
+    let mut add = |name: &str| {
+        if !name.is_empty() && name.len() <= MAX_NAME_LEN && !names.iter().any(|n| n == name) {
+            names.push(name.to_owned());
+        }
+    };
+
+    for token in tokenize(html) {
+        match &token {
+            Token::StartTag { name, .. } if matches!(name.as_str(), "input" | "select" | "textarea" | "button") => {
+                token.attr("name").into_iter().chain(token.attr("id")).for_each(&mut add);
+            }
+            Token::Text {
+                text,
+                kind: TextKind::Script,
+            } => {
+                for pattern in [&re.variable, &re.key, &re.query_key] {
+                    pattern.captures_iter(text).for_each(|cap| add(&cap[1]));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    names
+}
+
+struct Patterns {
+    variable: Regex,
+    key: Regex,
+    query_key: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+
+    PATTERNS.get_or_init(|| Patterns {
+        variable: Regex::new(r"\b(?:var|let|const)\s+([A-Za-z_$][\w$]*)").expect("invalid variable pattern"),
+        key: Regex::new(r#"["']?([A-Za-z_][\w\-]*)["']?\s*:\s*["'\d\[{tfn]"#).expect("invalid key pattern"),
+        query_key: Regex::new(r"[?&]([A-Za-z_][\w\-\[\]]*)=").expect("invalid query key pattern"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_wordlists() {
+        let contents = "# common names\nid\n\n  q  \n# debug flags\ndebug\n";
+        assert_eq!(wordlist(contents), ["id", "q", "debug"]);
+    }
+
+    #[test]
+    fn strips_echoed_params() {
+        let params = vec![
+            ("id".to_owned(), "kq1canary1".to_owned()),
+            ("debug".to_owned(), "kq1canary2".to_owned()),
+        ];
+        let body = "<a href=\"/page?x=1&amp;id=kq1canary1&debug=kq1canary2\">x</a><p>kq1canary2</p>";
+        assert_eq!(strip_params(body, &params), "<a href=\"/page?x=1\">x</a><p></p>");
+        assert_eq!(strip_params(body, &[]), body);
+    }
+
+    // finds the names an endpoint that reflects `reflected` and changes
+    // when sent `changing` reads, and how many requests that took
+    fn bisect(names: &[&str], reflected: &str, changing: &str) -> (Vec<String>, usize) {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        let mut bisection = Bisection::new(&names);
+        let mut requests = 0;
+
+        while let Some(params) = bisection.next_batch() {
+            requests += 1;
+            let body: String = params
+                .iter()
+                .filter(|(name, _)| name == reflected)
+                .map(|(_, canary)| canary.clone())
+                .collect();
+            let changed = params.iter().any(|(name, _)| name == changing);
+            bisection.record(params, &body, changed);
+        }
+
+        (bisection.found(), requests)
+    }
+
+    #[test]
+    fn bisection_finds_reflected_and_changing_names() {
+        let mut names: Vec<String> = (0..BATCH_SIZE - 2).map(|i| format!("p{}", i)).collect();
+        names.insert(40, "id".to_owned());
+        names.insert(90, "debug".to_owned());
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+        let (mut found, requests) = bisect(&names, "id", "debug");
+        found.sort();
+        assert_eq!(found, ["debug", "id"]);
+        // halving takes far fewer requests than one per name
+        assert!(requests <= 16, "{} requests", requests);
+    }
+
+    #[test]
+    fn finds_form_control_names() {
+        let html = r#"<form><input name="user" id="user-field"><select name="sort"></select>
+            <textarea id="comment"></textarea><button name="go">Go</button><div id="layout"></div></form>"#;
+        assert_eq!(page_params(html), ["user", "user-field", "sort", "comment", "go"]);
+    }
+
+    #[test]
+    fn finds_script_names() {
+        let html = r#"<script>
+            var theme = "dark"; let page_size = 10; const $cb = 1;
+            var cfg = {"debug": false, preview: 1, 'lang': 'en'};
+            fetch('/api/items?category=books&sort=asc');
+        </script><p>var notScript = 1;</p>"#;
+        let names = page_params(html);
+        for name in ["theme", "page_size", "$cb", "cfg", "debug", "preview", "lang", "category", "sort"] {
+            assert!(names.iter().any(|n| n == name), "{} missing from {:?}", name, names);
+        }
+        assert!(!names.iter().any(|n| n == "notScript"));
+    }
+
+    #[test]
+    fn page_names_are_unique_and_short() {
+        let long = "x".repeat(MAX_NAME_LEN + 1);
+        let html = format!(r#"<input name="q"><input name="q"><input name="{}"><script>var q = 1;</script>"#, long);
+        assert_eq!(page_params(&html), ["q"]);
+    }
+
+    #[test]
+    fn bisection_stops_when_nothing_changes() {
+        assert_eq!(bisect(&["a", "b", "c"], "", ""), (Vec::new(), 1));
+        assert_eq!(bisect(&["a", "b", "c"], "b", ""), (vec!["b".to_owned()], 1));
+    }
+}
@@ -0,0 +1,364 @@
+use serde_json::{json, Value};
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::reflection::ReflectionContext;
+use crate::result::{Confidence, ScanResult};
+use crate::Error;
+
+// the SARIF rules, one per reflection context plus one for DOM flows:
+// (id, name, description)
+const RULES: [(&str, &str, &str); 9] = [
+    ("xss-element-body", "XssInElementBody", "Cross-site scripting through markup injected into an element body"),
+    ("xss-attribute-name", "XssInAttributeName", "Cross-site scripting through an injected attribute"),
+    ("xss-attribute-value", "XssInAttributeValue", "Cross-site scripting through an attribute value"),
+    ("xss-script", "XssInScript", "Cross-site scripting through code injected into a script block"),
+    ("xss-script-string", "XssInScriptString", "Cross-site scripting by breaking out of a JavaScript string"),
+    ("xss-style", "XssInStyle", "Cross-site scripting by breaking out of a style block"),
+    ("xss-comment", "XssInComment", "Cross-site scripting by breaking out of an HTML comment"),
+    ("xss-rcdata", "XssInRcdata", "Cross-site scripting by breaking out of a textarea or title"),
+    ("dom-xss", "DomXss", "DOM-based cross-site scripting from a source flowing into a sink"),
+];
+
+/// How scan results are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One line of text per finding.
+    Text,
+    /// One JSON object per finding, see [`jsonl`].
+    Jsonl,
+    /// A single SARIF 2.1.0 log, written as results come in, see
+    /// [`SarifWriter`].
+    Sarif,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "sarif" => Ok(OutputFormat::Sarif),
+            _ => Err(Error::from(format!("Unknown output format: {}", s))),
+        }
+    }
+}
+
+/// Writes `result` as JSON Lines, one object per finding.
+///
+/// Every object has the same keys, set to `null` when they don't apply:
+/// `url`, `method`, `type` (`reflected`, `stored`, `dom` or `skipped`),
+/// `parameter`, `payload`, `context`, `status_code`, `confidence`,
+/// `snippet`, `observed_url` and `error`. A `skipped` object is a probe or
+/// payload the host kept pushing back on, with the reason in `error`. A
+/// result without
+/// findings is written as a single object whose `type` is `null`.
+pub fn jsonl(result: &ScanResult) -> String {
+    let records = records(result);
+    let lines: Vec<String> = records.iter().map(Value::to_string).collect();
+    lines.join("\n")
+}
+
+// the objects `jsonl` writes for `result`
+fn records(result: &ScanResult) -> Vec<Value> {
+    let record = |kind: Option<&str>| {
+        json!({
+            "url": result.url,
+            "method": result.method.as_ref().map(ToString::to_string),
+            "type": kind,
+            "parameter": null,
+            "payload": null,
+            "context": null,
+            "status_code": result.status.map(|status| status.as_u16()),
+            "confidence": null,
+            "snippet": null,
+            "observed_url": null,
+            "error": result.error,
+        })
+    };
+    let mut records = Vec::new();
+
+    for finding in &result.findings {
+        let mut value = record(Some("reflected"));
+        value["parameter"] = json!(finding.point.to_string());
+        value["payload"] = json!(finding.payload);
+        value["context"] = json!(finding.reflection.context.to_string());
+        value["status_code"] = json!(finding.status.as_u16());
+        value["confidence"] = json!(finding.confidence().to_string());
+        value["snippet"] = json!(finding.snippet);
+        records.push(value);
+    }
+
+    for stored in &result.stored {
+        let mut value = record(Some("stored"));
+        value["parameter"] = json!(stored.point.to_string());
+        value["payload"] = json!(stored.payload);
+        value["context"] = json!(stored.reflection.context.to_string());
+        value["status_code"] = json!(stored.status.as_u16());
+        value["confidence"] = json!(stored.confidence().to_string());
+        value["snippet"] = json!(stored.snippet);
+        value["observed_url"] = json!(stored.observed_url);
+        records.push(value);
+    }
+
+    for flow in &result.dom_flows {
+        let mut value = record(Some("dom"));
+        value["parameter"] = json!(flow.source);
+        value["context"] = json!(flow.sink);
+        value["confidence"] = json!(flow.confidence.to_string());
+        value["snippet"] = json!(flow.statement);
+        records.push(value);
+    }
+
+    for skipped in &result.skipped {
+        let mut value = record(Some("skipped"));
+        value["parameter"] = json!(skipped.point.to_string());
+        value["payload"] = json!(skipped.payload);
+        value["error"] = json!(skipped.reason);
+        records.push(value);
+    }
+
+    if records.is_empty() {
+        records.push(record(None));
+    }
+
+    records
+}
+
+/// Writes every finding as a SARIF 2.1.0 log with a single run, one scan
+/// result at a time, so nothing but the current result is held in memory.
+///
+/// Each finding becomes a result whose rule is the context it landed in,
+/// whose level follows its confidence and whose location is the scanned
+/// URL. Its code flow goes from the request that carried the payload to the
+/// part of the response it showed up in.
+///
+/// The log is only valid JSON once [`finish`](Self::finish) has been called.
+pub struct SarifWriter<W: Write> {
+    out: W,
+    empty: bool,
+}
+
+impl<W: Write> SarifWriter<W> {
+    /// Starts the log, writing everything up to its first result to `out`.
+    pub fn new(mut out: W) -> Result<Self, Error> {
+        // the run's results come before its tool, so they can be streamed
+        // without knowing where the log ends
+        let log = sarif_log();
+        write!(
+            out,
+            "{{\n  \"$schema\": {},\n  \"version\": {},\n  \"runs\": [{{\n    \"results\": [",
+            log["$schema"], log["version"]
+        )?;
+        Ok(SarifWriter { out, empty: true })
+    }
+
+    /// Writes the findings in `result`, if any.
+    pub fn write(&mut self, result: &ScanResult) -> Result<(), Error> {
+        for value in sarif_results(result) {
+            let separator = if self.empty { "" } else { "," };
+            write!(self.out, "{}\n      {}", separator, value)?;
+            self.empty = false;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+
+    /// Ends the log and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        let tool = &sarif_log()["runs"][0]["tool"];
+        let indent = if self.empty { "" } else { "\n    " };
+        writeln!(self.out, "{}],\n    \"tool\": {}\n  }}]\n}}", indent, tool)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+// the SARIF log without any results
+fn sarif_log() -> Value {
+    let rules: Vec<Value> = RULES
+        .iter()
+        .map(|(id, name, description)| {
+            json!({
+                "id": id,
+                "name": name,
+                "shortDescription": { "text": description },
+                "defaultConfiguration": { "level": "error" },
+                "properties": { "tags": ["security", "xss"], "security-severity": "8.0" },
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "crabxss",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "results": [],
+        }],
+    })
+}
+
+// the SARIF results for every finding in `result`
+fn sarif_results(result: &ScanResult) -> Vec<Value> {
+    let mut sarif_results = Vec::new();
+
+    for finding in &result.findings {
+        let message = format!(
+            "Payload '{}' reflected in {} via {}",
+            finding.payload, finding.reflection.context, finding.point
+        );
+        sarif_results.push(sarif_result(
+            context_rule(finding.reflection.context),
+            finding.confidence(),
+            &message,
+            &result.url,
+            &[
+                (finding.request.url.as_str(), format!("Request:\n{}", finding.request.to_raw())),
+                (result.url.as_str(), format!("Response ({}):\n{}", finding.status, finding.snippet)),
+            ],
+        ));
+    }
+
+    for stored in &result.stored {
+        let message = format!(
+            "Payload '{}' planted via {} shows up in {} at {}",
+            stored.payload, stored.point, stored.reflection.context, stored.observed_url
+        );
+        sarif_results.push(sarif_result(
+            context_rule(stored.reflection.context),
+            stored.confidence(),
+            &message,
+            &result.url,
+            &[
+                (stored.request.url.as_str(), format!("Request:\n{}", stored.request.to_raw())),
+                (stored.observed_url.as_str(), format!("Response ({}):\n{}", stored.status, stored.snippet)),
+            ],
+        ));
+    }
+
+    for flow in &result.dom_flows {
+        let message = format!("{} flows into {} in {}", flow.source, flow.sink, flow.script);
+        sarif_results.push(sarif_result(
+            "dom-xss",
+            flow.confidence,
+            &message,
+            &result.url,
+            &[(result.url.as_str(), flow.statement.clone())],
+        ));
+    }
+
+    sarif_results
+}
+
+// a SARIF result for rule `rule` at `url`, with `steps` as its code flow
+fn sarif_result(rule: &str, confidence: Confidence, message: &str, url: &str, steps: &[(&str, String)]) -> Value {
+    let physical_location = |uri: &str| json!({ "artifactLocation": { "uri": uri } });
+    let steps: Vec<Value> = steps
+        .iter()
+        .map(|(uri, text)| {
+            json!({
+                "location": {
+                    "physicalLocation": physical_location(uri),
+                    "message": { "text": text },
+                }
+            })
+        })
+        .collect();
+
+    json!({
+        "ruleId": rule,
+        "ruleIndex": RULES.iter().position(|(id, _, _)| *id == rule),
+        "level": match confidence {
+            Confidence::Confirmed | Confidence::High => "error",
+            Confidence::Medium => "warning",
+            Confidence::Low => "note",
+        },
+        "message": { "text": message },
+        "locations": [{ "physicalLocation": physical_location(url) }],
+        "codeFlows": [{ "threadFlows": [{ "locations": steps }] }],
+        "properties": { "confidence": confidence.to_string() },
+    })
+}
+
+fn context_rule(context: ReflectionContext) -> &'static str {
+    match context {
+        ReflectionContext::ElementBody => "xss-element-body",
+        ReflectionContext::AttributeName => "xss-attribute-name",
+        ReflectionContext::AttributeValue(_) => "xss-attribute-value",
+        ReflectionContext::Script => "xss-script",
+        ReflectionContext::ScriptString(_) => "xss-script-string",
+        ReflectionContext::Style => "xss-style",
+        ReflectionContext::Comment => "xss-comment",
+        ReflectionContext::Rcdata => "xss-rcdata",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::find_flows;
+    use crate::inject::InjectionPoint;
+    use crate::reflection::Reflection;
+    use crate::request::RequestTemplate;
+    use crate::result::Finding;
+    use reqwest::StatusCode;
+    use url::Url;
+
+    fn finding(name: &str, payload: &str) -> Finding {
+        Finding {
+            point: InjectionPoint::Query { index: 0, name: name.to_owned() },
+            payload: payload.to_owned(),
+            request: RequestTemplate::get(Url::parse("https://example.com/?q=1").unwrap()),
+            reflection: Reflection {
+                context: ReflectionContext::ElementBody,
+                element: None,
+                attribute: None,
+                exploitable: true,
+            },
+            status: StatusCode::OK,
+            confirmed: false,
+            snippet: format!("<p>{}</p>", payload),
+        }
+    }
+
+    fn sarif(results: &[ScanResult]) -> Value {
+        let mut writer = SarifWriter::new(Vec::new()).unwrap();
+        for result in results {
+            writer.write(result).unwrap();
+        }
+        let out = writer.finish().unwrap();
+        serde_json::from_slice(&out).expect("the log is valid JSON")
+    }
+
+    #[test]
+    fn sarif_log_without_results() {
+        let log = sarif(&[ScanResult::new("https://example.com/")]);
+        assert_eq!(log["version"], "2.1.0");
+        assert_eq!(log["runs"][0]["results"], json!([]));
+        assert_eq!(log["runs"][0]["tool"]["driver"]["name"], "crabxss");
+    }
+
+    #[test]
+    fn sarif_log_with_several_results() {
+        let mut first = ScanResult::new("https://example.com/?q=1");
+        first.findings.push(finding("q", "<svg onload=alert(1)>"));
+        first.findings.push(finding("s", "\"quoted\" \\ payload"));
+        let mut second = ScanResult::new("https://example.com/app");
+        second.dom_flows = find_flows("document.body.innerHTML = location.hash;", "inline script #1");
+
+        let log = sarif(&[first, ScanResult::new("https://example.com/empty"), second]);
+        let results = log["runs"][0]["results"].as_array().expect("results is an array");
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["ruleId"], "xss-element-body");
+        assert_eq!(results[2]["ruleId"], "dom-xss");
+        assert_eq!(log["runs"].as_array().map(Vec::len), Some(1));
+    }
+}
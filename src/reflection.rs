@@ -0,0 +1,512 @@
+use regex::Regex;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::html::{tokenize, TextKind, Token};
+use crate::js::{self, JsState};
+
+// bytes of response kept on each side of a reflection in an excerpt
+const EXCERPT_CONTEXT: usize = 80;
+
+/// Where in the document a reflected value landed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReflectionContext {
+    /// Text content of a regular element.
+    ElementBody,
+    /// The name of an attribute.
+    AttributeName,
+    /// The value of an attribute, with the quote style it was written in.
+    AttributeValue(Quote),
+    /// Code in the body of a `<script>` element.
+    Script,
+    /// A string literal in the body of a `<script>` element, with its quote.
+    ScriptString(char),
+    /// The body of a `<style>` or other raw text element.
+    Style,
+    /// An HTML comment.
+    Comment,
+    /// The body of a `<textarea>` or `<title>`.
+    Rcdata,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quote {
+    Double,
+    Single,
+    Unquoted,
+}
+
+impl Quote {
+    pub fn as_char(&self) -> Option<char> {
+        match self {
+            Quote::Double => Some('"'),
+            Quote::Single => Some('\''),
+            Quote::Unquoted => None,
+        }
+    }
+}
+
+impl fmt::Display for ReflectionContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ReflectionContext::ElementBody => "element body",
+            ReflectionContext::AttributeName => "attribute name",
+            ReflectionContext::AttributeValue(Quote::Double) => "double-quoted attribute value",
+            ReflectionContext::AttributeValue(Quote::Single) => "single-quoted attribute value",
+            ReflectionContext::AttributeValue(Quote::Unquoted) => "unquoted attribute value",
+            ReflectionContext::Script => "script block",
+            ReflectionContext::ScriptString(quote) => return write!(f, "script string ({})", quote),
+            ReflectionContext::Style => "style block",
+            ReflectionContext::Comment => "comment",
+            ReflectionContext::Rcdata => "RCDATA",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A single place where a value showed up in a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reflection {
+    pub context: ReflectionContext,
+    /// The element the value was found in or injected as.
+    pub element: Option<String>,
+    /// The attribute the value was found in or injected as.
+    pub attribute: Option<String>,
+    /// Whether the value changed the structure of the document, i.e. it
+    /// created an element or attribute instead of being read as data, or
+    /// broke out into running script.
+    pub exploitable: bool,
+}
+
+/// Evidence in a response that a payload did more than get reflected as data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Signature {
+    /// The payload created an element with these attributes, optionally
+    /// followed by this text.
+    Element {
+        name: String,
+        attrs: Vec<(String, String)>,
+        text: Option<String>,
+    },
+    /// The payload added an event handler attribute to an existing element.
+    Handler { name: String, value: String },
+    /// The payload turned attribute `attribute` into a `javascript:` URL.
+    JavascriptUrl { attribute: String, code: String },
+    /// The `code` part of the payload runs as script, either in a
+    /// `<script>` block or an event handler.
+    ScriptCode { code: String },
+}
+
+/// Derives the signatures of an arbitrary payload: the elements it would
+/// create and the event handlers it would add if reflected verbatim.
+pub fn signatures(payload: &str) -> Vec<Signature> {
+    let mut signatures = Vec::new();
+
+    let tokens = tokenize(payload);
+    let mut iter = tokens.into_iter().peekable();
+    while let Some(token) = iter.next() {
+        if let Token::StartTag { name, attrs } = token {
+            let text = match iter.peek() {
+                Some(Token::Text { text, .. }) => Some(text.clone()),
+                _ => None,
+            };
+            // a bare tag like `<b>` proves nothing on its own
+            if !attrs.is_empty() || text.is_some() {
+                signatures.push(Signature::Element { name, attrs, text });
+            }
+        }
+    }
+
+    // handlers injected into an existing tag, e.g. `" onfocus=alert(1) x="`
+    for cap in handler_pattern().captures_iter(payload) {
+        signatures.push(Signature::Handler {
+            name: cap[1].to_ascii_lowercase(),
+            value: cap[2].to_owned(),
+        });
+    }
+
+    signatures
+}
+
+fn handler_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r#"(?i)(?:^|[\s"'/])(on[a-z]+)\s*=\s*([^\s>"']+)"#).expect("invalid handler pattern")
+    })
+}
+
+/// Finds every place `payload` was reflected in `body`, using the
+/// signatures derived from the payload itself.
+pub fn analyze(body: &str, payload: &str) -> Vec<Reflection> {
+    analyze_with(body, payload, &signatures(payload))
+}
+
+/// Finds every place `payload` was reflected in `body`.
+///
+/// Reflections where the payload is read as text, an attribute value or a
+/// comment are reported as not exploitable. A reflection is exploitable only
+/// when one of `signatures` shows up in the tokenized response.
+pub fn analyze_with(body: &str, payload: &str, signatures: &[Signature]) -> Vec<Reflection> {
+    let tokens = tokenize(body);
+    let mut reflections = structural_reflections(body, &tokens, payload, signatures);
+    let mut element: Option<&str> = None;
+
+    for token in &tokens {
+        match token {
+            Token::Text { text, kind } => {
+                let Some(pos) = text.find(payload) else {
+                    continue;
+                };
+                let context = match kind {
+                    TextKind::Data => ReflectionContext::ElementBody,
+                    TextKind::Script => match js::state_at(text, pos) {
+                        JsState::String(quote) => ReflectionContext::ScriptString(quote),
+                        _ => ReflectionContext::Script,
+                    },
+                    TextKind::Rawtext => ReflectionContext::Style,
+                    TextKind::Rcdata => ReflectionContext::Rcdata,
+                };
+                reflections.push(Reflection {
+                    context,
+                    element: element.map(str::to_owned),
+                    attribute: None,
+                    exploitable: false,
+                });
+            }
+            Token::Comment(text) if text.contains(payload) => {
+                reflections.push(Reflection {
+                    context: ReflectionContext::Comment,
+                    element: None,
+                    attribute: None,
+                    exploitable: false,
+                });
+            }
+            Token::StartTag { name, attrs } => {
+                element = Some(name);
+                for (attr, value) in attrs {
+                    let context = if attr.contains(payload) {
+                        ReflectionContext::AttributeName
+                    } else if value.contains(payload) {
+                        ReflectionContext::AttributeValue(attribute_quote(body, attr, payload))
+                    } else {
+                        continue;
+                    };
+                    reflections.push(Reflection {
+                        context,
+                        element: Some(name.clone()),
+                        attribute: Some(attr.clone()),
+                        exploitable: false,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    reflections
+}
+
+// looks for the signatures of the payload in the response
+fn structural_reflections(body: &str, tokens: &[Token], payload: &str, signatures: &[Signature]) -> Vec<Reflection> {
+    let mut reflections = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::StartTag { name, attrs } => {
+                for signature in signatures {
+                    let reflection = match signature {
+                        Signature::Element {
+                            name: sig_name,
+                            attrs: sig_attrs,
+                            text,
+                        } if sig_name == name
+                            && sig_attrs.iter().all(|attr| attrs.contains(attr))
+                            && match (text, tokens.get(i + 1)) {
+                                (None, _) => true,
+                                (Some(expected), Some(Token::Text { text, .. })) => text.starts_with(expected.as_str()),
+                                (Some(_), _) => false,
+                            } =>
+                        {
+                            Some((ReflectionContext::ElementBody, None))
+                        }
+                        // the handler is new, but the payload was injected into
+                        // the attribute value it broke out of
+                        Signature::Handler { name: handler, value } if attrs.contains(&(handler.clone(), value.clone())) => {
+                            Some((ReflectionContext::AttributeValue(breakout_quote(payload)), Some(handler.clone())))
+                        }
+                        Signature::JavascriptUrl { attribute, code } => attrs
+                            .iter()
+                            .find(|(attr, value)| {
+                                attr == attribute
+                                    && value.trim_start().to_ascii_lowercase().starts_with("javascript:")
+                                    && value.contains(payload)
+                                    && value.contains(code.as_str())
+                            })
+                            .map(|(attr, _)| {
+                                let quote = attribute_quote(body, attr, payload);
+                                (ReflectionContext::AttributeValue(quote), Some(attr.clone()))
+                            }),
+                        Signature::ScriptCode { code } => attrs
+                            .iter()
+                            .find(|(attr, value)| attr.starts_with("on") && js::runs_as_code(value, payload, code))
+                            .map(|(attr, _)| {
+                                let quote = attribute_quote(body, attr, payload);
+                                (ReflectionContext::AttributeValue(quote), Some(attr.clone()))
+                            }),
+                        _ => None,
+                    };
+
+                    if let Some((context, attribute)) = reflection {
+                        reflections.push(Reflection {
+                            context,
+                            element: Some(name.clone()),
+                            attribute,
+                            exploitable: true,
+                        });
+                        break;
+                    }
+                }
+            }
+            Token::Text {
+                text,
+                kind: TextKind::Script,
+            } => {
+                let runs = signatures.iter().any(|signature| match signature {
+                    Signature::ScriptCode { code } => js::runs_as_code(text, payload, code),
+                    _ => false,
+                });
+                if runs {
+                    reflections.push(Reflection {
+                        context: ReflectionContext::Script,
+                        element: Some("script".to_owned()),
+                        attribute: None,
+                        exploitable: true,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    reflections
+}
+
+// the quote of the attribute value that `payload` breaks out of, judging by
+// the quote it starts with
+fn breakout_quote(payload: &str) -> Quote {
+    match payload.trim_start().chars().next() {
+        Some('"') => Quote::Double,
+        Some('\'') => Quote::Single,
+        _ => Quote::Unquoted,
+    }
+}
+
+// finds how the value of `attr` containing `needle` was quoted in the raw
+// response, since the tokenizer doesn't keep the quotes
+fn attribute_quote(body: &str, attr: &str, needle: &str) -> Quote {
+    // the value may be entity-encoded in the raw response, so fall back to
+    // its longest alphanumeric run
+    let needle = if body.contains(needle) {
+        needle
+    } else {
+        match needle
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .max_by_key(|run| run.len())
+        {
+            Some(run) if run.len() >= 4 => run,
+            _ => return Quote::Double,
+        }
+    };
+
+    for (pos, _) in body.match_indices(needle) {
+        let Some(tag_start) = body[..pos].rfind('<') else {
+            continue;
+        };
+        if let Some(quote) = quote_at(&body[tag_start..], attr, pos - tag_start) {
+            return quote;
+        }
+    }
+
+    Quote::Double
+}
+
+// walks the attributes of the tag starting at `tag` and returns the quote
+// style of `attr` if its value spans `offset`
+fn quote_at(tag: &str, attr: &str, offset: usize) -> Option<Quote> {
+    let bytes = tag.as_bytes();
+    let mut i = 1;
+
+    // skip the tag name
+    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+        i += 1;
+    }
+
+    while i < bytes.len() && i <= offset {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+        if i >= bytes.len() || bytes[i] == b'>' {
+            return None;
+        }
+
+        let name_start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !matches!(bytes[i], b'=' | b'>') {
+            i += 1;
+        }
+        let name = &tag[name_start..i];
+
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= bytes.len() || bytes[i] != b'=' {
+            continue;
+        }
+        i += 1;
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+
+        let (quote, value_start) = match bytes.get(i) {
+            Some(b'"') => (Quote::Double, i + 1),
+            Some(b'\'') => (Quote::Single, i + 1),
+            _ => (Quote::Unquoted, i),
+        };
+        let value_end = match quote {
+            Quote::Double => tag[value_start..].find('"'),
+            Quote::Single => tag[value_start..].find('\''),
+            Quote::Unquoted => tag[value_start..].find(|c: char| c.is_ascii_whitespace() || c == '>'),
+        }
+        .map(|end| value_start + end)
+        .unwrap_or(tag.len());
+
+        if name.eq_ignore_ascii_case(attr) && (value_start..=value_end).contains(&offset) {
+            return Some(quote);
+        }
+
+        i = if quote == Quote::Unquoted { value_end } else { value_end + 1 };
+    }
+
+    None
+}
+
+/// The part of `body` around the first reflection of `payload`, or around
+/// its longest alphanumeric run when the payload came back encoded.
+pub fn excerpt(body: &str, payload: &str) -> String {
+    let Some((start, len)) = locate(body, payload) else {
+        return String::new();
+    };
+
+    let mut from = start.saturating_sub(EXCERPT_CONTEXT);
+    while !body.is_char_boundary(from) {
+        from -= 1;
+    }
+    let mut to = (start + len + EXCERPT_CONTEXT).min(body.len());
+    while !body.is_char_boundary(to) {
+        to += 1;
+    }
+
+    body[from..to].to_owned()
+}
+
+/// The byte offset and length of the first reflection of `payload` in
+/// `body`, falling back to its longest alphanumeric run when the payload
+/// came back encoded.
+pub fn locate(body: &str, payload: &str) -> Option<(usize, usize)> {
+    if payload.is_empty() {
+        return None;
+    }
+    if let Some(start) = body.find(payload) {
+        return Some((start, payload.len()));
+    }
+
+    let longest_word = payload
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .max_by_key(|word| word.len())
+        .filter(|word| !word.is_empty())?;
+    body.find(longest_word).map(|start| (start, longest_word.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CANARY: &str = "zq7canary1";
+
+    fn contexts(body: &str) -> Vec<ReflectionContext> {
+        analyze(body, CANARY).into_iter().map(|reflection| reflection.context).collect()
+    }
+
+    #[test]
+    fn classifies_every_context() {
+        let cases = [
+            ("<p>zq7canary1</p>", ReflectionContext::ElementBody),
+            ("<p zq7canary1>x</p>", ReflectionContext::AttributeName),
+            (r#"<a title="x zq7canary1">x</a>"#, ReflectionContext::AttributeValue(Quote::Double)),
+            ("<a title='zq7canary1'>x</a>", ReflectionContext::AttributeValue(Quote::Single)),
+            ("<a title=zq7canary1>x</a>", ReflectionContext::AttributeValue(Quote::Unquoted)),
+            ("<script>var a = zq7canary1;</script>", ReflectionContext::Script),
+            (r#"<script>var a = "zq7canary1";</script>"#, ReflectionContext::ScriptString('"')),
+            ("<script>var a = 'zq7canary1';</script>", ReflectionContext::ScriptString('\'')),
+            ("<script>var a = `zq7canary1`;</script>", ReflectionContext::ScriptString('`')),
+            ("<style>p { color: zq7canary1 }</style>", ReflectionContext::Style),
+            ("<!-- zq7canary1 -->", ReflectionContext::Comment),
+            ("<textarea>zq7canary1</textarea>", ReflectionContext::Rcdata),
+            ("<title>zq7canary1</title>", ReflectionContext::Rcdata),
+        ];
+
+        for (body, expected) in cases {
+            assert_eq!(contexts(body), vec![expected], "{}", body);
+        }
+    }
+
+    #[test]
+    fn unreflected_canary_has_no_context() {
+        assert!(contexts("<p>nothing here</p>").is_empty());
+    }
+
+    #[test]
+    fn injected_element_is_exploitable() {
+        let payload = "<svg onload=alert(1)>";
+        let reflections = analyze(&format!("<p>{}</p>", payload), payload);
+        assert!(reflections.iter().any(|r| r.exploitable && r.context == ReflectionContext::ElementBody));
+
+        let encoded = analyze("<p>&lt;svg onload=alert(1)&gt;</p>", payload);
+        assert!(encoded.iter().all(|r| !r.exploitable));
+    }
+
+    #[test]
+    fn handler_breakout_is_reported_in_attribute_value() {
+        let payload = r#"" autofocus onfocus=alert(1) x=""#;
+        let body = format!(r#"<input value="{}">"#, payload);
+        let reflection = analyze(&body, payload).into_iter().find(|r| r.exploitable).expect("handler is injected");
+        assert_eq!(reflection.context, ReflectionContext::AttributeValue(Quote::Double));
+        assert_eq!(reflection.attribute.as_deref(), Some("onfocus"));
+
+        let payload = " onfocus=alert(1) autofocus";
+        let body = format!("<input value=x{}>", payload);
+        let reflection = analyze(&body, payload).into_iter().find(|r| r.exploitable).expect("handler is injected");
+        assert_eq!(reflection.context, ReflectionContext::AttributeValue(Quote::Unquoted));
+    }
+
+    #[test]
+    fn quote_skips_quoted_lookalikes() {
+        let body = r#"<a title="x href='y'" href='zq7canary1'>x</a>"#;
+        assert_eq!(attribute_quote(body, "href", CANARY), Quote::Single);
+
+        let body = r#"<a title='a "b"' href = "zq7canary1">x</a>"#;
+        assert_eq!(attribute_quote(body, "href", CANARY), Quote::Double);
+    }
+
+    #[test]
+    fn quote_of_encoded_value_uses_its_longest_run() {
+        let body = r#"<input value='&quot;zq7canary1'>"#;
+        assert_eq!(attribute_quote(body, "value", "\"zq7canary1"), Quote::Single);
+    }
+
+    #[test]
+    fn locates_payload_or_its_longest_run() {
+        assert_eq!(locate("ab<x>cd", "<x>"), Some((2, 3)));
+        assert_eq!(locate("ab&lt;xyzw&gt;", "<xyzw>"), Some((6, 4)));
+        assert_eq!(locate("nothing", "<xyzw>"), None);
+    }
+}
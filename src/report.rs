@@ -0,0 +1,316 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use url::Url;
+
+use crate::reflection::locate;
+use crate::result::{Confidence, ScanResult};
+
+// no scripts may run in the report, whatever the findings contain
+const CONTENT_SECURITY_POLICY: &str = "default-src 'none'; style-src 'unsafe-inline'";
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 72em; color: #222; }
+h2 { border-bottom: 2px solid #ddd; padding-bottom: .2em; margin-top: 2em; }
+h3 { font-family: monospace; font-size: 1em; background: #f4f4f4; padding: .4em; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid #ddd; padding: .3em .8em; text-align: left; }
+.finding { border-left: 4px solid #999; padding: .2em 1em; margin: 1em 0; }
+.finding.confirmed, .finding.high { border-color: #c0392b; }
+.finding.medium { border-color: #e67e22; }
+.finding.low { border-color: #7f8c8d; }
+.badge { font-size: .8em; font-weight: bold; text-transform: uppercase; padding: .1em .4em; background: #eee; }
+pre { background: #fafafa; border: 1px solid #ddd; padding: .6em; white-space: pre-wrap; word-break: break-all; }
+pre.repro { user-select: all; }
+mark { background: #ffe066; }
+";
+
+// one finding as shown in the report
+struct Entry {
+    kind: &'static str,
+    confidence: Confidence,
+    title: String,
+    payload: Option<String>,
+    excerpt: String,
+    // what to highlight in the excerpt
+    needle: String,
+    repro: Option<String>,
+}
+
+/// Collects results as they come in for an HTML report. Only the entries
+/// it will show are kept, not the results themselves, so a long scan
+/// doesn't pile up in memory.
+#[derive(Default)]
+pub struct HtmlReport {
+    scanned: usize,
+    // host -> endpoint -> entries
+    hosts: BTreeMap<String, BTreeMap<String, Vec<Entry>>>,
+    // (url, error)
+    errors: Vec<(String, String)>,
+}
+
+impl HtmlReport {
+    pub fn add(&mut self, result: ScanResult) {
+        self.scanned += 1;
+
+        if let Some(error) = &result.error {
+            self.errors.push((result.url.clone(), error.clone()));
+        }
+        for skipped in &result.skipped {
+            self.errors.push((result.url.clone(), skipped.to_string()));
+        }
+
+        let page = result.method.as_ref().map(ToString::to_string).unwrap_or_else(|| "GET".to_owned());
+        let hosts = &mut self.hosts;
+        let mut add = |method: &str, url: &str, entry: Entry| {
+            let (host, endpoint) = endpoint(method, url);
+            hosts.entry(host).or_default().entry(endpoint).or_default().push(entry);
+        };
+
+        for finding in &result.findings {
+            add(
+                finding.request.method.as_str(),
+                finding.request.url.as_str(),
+                Entry {
+                    kind: "Reflected XSS",
+                    confidence: finding.confidence(),
+                    title: format!(
+                        "{} reflected in {} ({})",
+                        finding.point, finding.reflection.context, finding.status
+                    ),
+                    payload: Some(finding.payload.clone()),
+                    excerpt: finding.snippet.clone(),
+                    needle: finding.payload.clone(),
+                    repro: Some(finding.request.to_raw()),
+                },
+            );
+        }
+
+        for stored in &result.stored {
+            add(
+                stored.request.method.as_str(),
+                stored.request.url.as_str(),
+                Entry {
+                    kind: "Stored XSS",
+                    confidence: stored.confidence(),
+                    title: format!(
+                        "{} shows up in {} at {} ({})",
+                        stored.point, stored.reflection.context, stored.observed_url, stored.status
+                    ),
+                    payload: Some(stored.payload.clone()),
+                    excerpt: stored.snippet.clone(),
+                    needle: stored.payload.clone(),
+                    repro: Some(stored.request.to_raw()),
+                },
+            );
+        }
+
+        for flow in &result.dom_flows {
+            add(
+                &page,
+                &result.url,
+                Entry {
+                    kind: "DOM XSS",
+                    confidence: flow.confidence,
+                    title: format!("{} flows into {} in {}", flow.source, flow.sink, flow.script),
+                    payload: None,
+                    excerpt: flow.statement.clone(),
+                    needle: flow.source.clone(),
+                    repro: None,
+                },
+            );
+        }
+    }
+
+    /// Renders the report as a single self-contained HTML page: summary
+    /// statistics, then every finding grouped by host and endpoint, with the
+    /// escaped response excerpt it was found in and the raw request that
+    /// reproduces it.
+    ///
+    /// Everything taken from the scan is HTML-escaped, and the page's content
+    /// security policy forbids scripts, so payloads are displayed but never
+    /// run.
+    pub fn render(&self) -> String {
+        let hosts = &self.hosts;
+        let entries = || hosts.values().flat_map(BTreeMap::values).flatten();
+        let count = |confidence: Confidence| entries().filter(|entry| entry.confidence == confidence).count();
+
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!(
+            "<meta http-equiv=\"Content-Security-Policy\" content=\"{}\">\n",
+            CONTENT_SECURITY_POLICY
+        ));
+        html.push_str("<title>crabxss report</title>\n");
+        html.push_str(&format!("<style>{}</style>\n</head>\n<body>\n<h1>crabxss report</h1>\n", STYLE));
+
+        html.push_str("<table>\n");
+        let stats = [
+            ("Requests scanned", self.scanned),
+            ("Errors", self.errors.len()),
+            ("Hosts with findings", hosts.len()),
+            ("Endpoints with findings", hosts.values().map(BTreeMap::len).sum()),
+            ("Findings", entries().count()),
+            ("Confirmed", count(Confidence::Confirmed)),
+            ("High confidence", count(Confidence::High)),
+            ("Medium confidence", count(Confidence::Medium)),
+            ("Low confidence", count(Confidence::Low)),
+        ];
+        for (label, value) in stats {
+            html.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", label, value));
+        }
+        html.push_str("</table>\n");
+
+        for (host, endpoints) in hosts {
+            html.push_str(&format!("<h2>{}</h2>\n", escape(host)));
+            for (endpoint, entries) in endpoints {
+                html.push_str(&format!("<h3>{}</h3>\n", escape(endpoint)));
+
+                let mut entries: Vec<&Entry> = entries.iter().collect();
+                entries.sort_by_key(|entry| Reverse(entry.confidence));
+                for entry in entries {
+                    html.push_str(&render_entry(entry));
+                }
+            }
+        }
+
+        if !self.errors.is_empty() {
+            html.push_str("<h2>Errors</h2>\n<table>\n");
+            for (url, error) in &self.errors {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td><pre>{}</pre></td></tr>\n",
+                    escape(url),
+                    escape(error)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+fn render_entry(entry: &Entry) -> String {
+    let mut html = format!("<div class=\"finding {}\">\n", entry.confidence);
+    html.push_str(&format!(
+        "<p><span class=\"badge\">{}</span> <strong>{}</strong>: {}</p>\n",
+        entry.confidence,
+        entry.kind,
+        escape(&entry.title)
+    ));
+    if let Some(payload) = &entry.payload {
+        html.push_str(&format!("<p>Payload: <code>{}</code></p>\n", escape(payload)));
+    }
+    html.push_str(&format!("<pre class=\"excerpt\">{}</pre>\n", highlight(&entry.excerpt, &entry.needle)));
+    if let Some(repro) = &entry.repro {
+        html.push_str(&format!(
+            "<p>Reproduction request:</p>\n<pre class=\"repro\">{}</pre>\n",
+            escape(&repro.replace("\r\n", "\n"))
+        ));
+    }
+    html.push_str("</div>\n");
+    html
+}
+
+// the host and `METHOD origin/path` of `url`, for grouping findings
+fn endpoint(method: &str, url: &str) -> (String, String) {
+    match Url::parse(url) {
+        Ok(parsed) => (
+            parsed.host_str().unwrap_or_default().to_owned(),
+            format!("{} {}{}", method, parsed.origin().ascii_serialization(), parsed.path()),
+        ),
+        Err(_) => (String::new(), format!("{} {}", method, url)),
+    }
+}
+
+// `excerpt`, escaped, with the reflection of `needle` marked
+fn highlight(excerpt: &str, needle: &str) -> String {
+    match locate(excerpt, needle) {
+        Some((start, len)) => format!(
+            "{}<mark>{}</mark>{}",
+            escape(&excerpt[..start]),
+            escape(&excerpt[start..start + len]),
+            escape(&excerpt[start + len..])
+        ),
+        None => escape(excerpt),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inject::InjectionPoint;
+    use crate::reflection::{Reflection, ReflectionContext};
+    use crate::request::RequestTemplate;
+    use crate::result::Finding;
+    use reqwest::StatusCode;
+
+    const PAYLOAD: &str = "<script>alert(1)</script>";
+
+    fn result() -> ScanResult {
+        let mut request = RequestTemplate::get(Url::parse("https://example.com/search?q=x").unwrap());
+        request.headers.push(("X-Test".to_owned(), PAYLOAD.to_owned()));
+        let mut result = ScanResult::new("https://example.com/search?q=x");
+        result.findings.push(Finding {
+            point: InjectionPoint::Header { name: PAYLOAD.to_owned() },
+            payload: PAYLOAD.to_owned(),
+            request,
+            reflection: Reflection {
+                context: ReflectionContext::ElementBody,
+                element: None,
+                attribute: None,
+                exploitable: true,
+            },
+            status: StatusCode::OK,
+            confirmed: true,
+            snippet: format!("<p>{}</p>", PAYLOAD),
+        });
+        result.error = Some(format!("error near {}", PAYLOAD));
+        result
+    }
+
+    #[test]
+    fn render_escapes_everything_from_the_scan() {
+        let mut report = HtmlReport::default();
+        report.add(result());
+        let html = report.render();
+
+        // title, payload, excerpt, reproduction request and error all
+        // carry the payload, and none of them may run it
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("header &#39;&lt;script&gt;alert(1)&lt;/script&gt;&#39; reflected in element body"));
+        assert!(html.contains("<code>&lt;script&gt;alert(1)&lt;/script&gt;</code>"));
+        assert!(html.contains("&lt;p&gt;<mark>&lt;script&gt;alert(1)&lt;/script&gt;</mark>&lt;/p&gt;"));
+        assert!(html.contains("X-Test: &lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains("error near &lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains(CONTENT_SECURITY_POLICY));
+    }
+
+    #[test]
+    fn highlight_escapes_encoded_reflections() {
+        let excerpt = "<p>&lt;img src=x onerror=alert(1)&gt;</p>";
+        assert_eq!(
+            highlight(excerpt, "<img src=x onerror=alert(1)>"),
+            "&lt;p&gt;&amp;lt;img src=x <mark>onerror</mark>=alert(1)&amp;gt;&lt;/p&gt;"
+        );
+        assert_eq!(highlight("<b>none</b>", "<svg>"), "&lt;b&gt;none&lt;/b&gt;");
+    }
+
+    #[test]
+    fn keeps_only_what_it_shows() {
+        let mut report = HtmlReport::default();
+        report.add(ScanResult::new("https://example.com/"));
+        report.add(result());
+        assert_eq!(report.scanned, 2);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.hosts["example.com"].len(), 1);
+    }
+}
@@ -0,0 +1,186 @@
+use reqwest::Method;
+use serde_json::Value;
+use url::form_urlencoded;
+use url::Url;
+
+use crate::{Error, Result};
+
+/// A request to scan: the injection points are applied to a copy of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestTemplate {
+    pub method: Method,
+    pub url: Url,
+    pub headers: Vec<(String, String)>,
+    pub body: Body,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Body {
+    Empty,
+    /// An `application/x-www-form-urlencoded` body.
+    Form(Vec<(String, String)>),
+    /// A JSON body.
+    Json(Value),
+    /// A `multipart/form-data` body.
+    Multipart(Vec<Part>),
+}
+
+/// A field of a `multipart/form-data` body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Part {
+    pub name: String,
+    pub value: String,
+    /// The file name, for file fields.
+    pub filename: Option<String>,
+}
+
+impl Part {
+    /// A file field named `name` that uploads a placeholder file named
+    /// `filename`.
+    pub fn file(name: impl Into<String>, filename: impl Into<String>) -> Self {
+        Part {
+            name: name.into(),
+            value: FILE_PLACEHOLDER.to_owned(),
+            filename: Some(filename.into()),
+        }
+    }
+
+    /// Parses a curl-style field: `name=value` for a text field, or
+    /// `name=@filename` for a file field named `filename`.
+    pub fn parse(field: &str) -> Option<Self> {
+        let (name, value) = field.split_once('=')?;
+
+        Some(match value.strip_prefix('@') {
+            Some(filename) => Part::file(name, filename),
+            None => Part {
+                name: name.to_owned(),
+                value: value.to_owned(),
+                filename: None,
+            },
+        })
+    }
+}
+
+const MULTIPART_BOUNDARY: &str = "----crabxss7MA4YWxkTrZu0gW";
+
+// the content of file fields, which isn't read from disk
+const FILE_PLACEHOLDER: &str = "crabxss";
+
+impl Body {
+    /// Parses an `application/x-www-form-urlencoded` body such as `a=1&b=2`.
+    pub fn form(data: &str) -> Self {
+        Body::Form(
+            form_urlencoded::parse(data.as_bytes())
+                .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                .collect(),
+        )
+    }
+
+    /// Parses a JSON body template.
+    pub fn json(data: &str) -> Result<Self> {
+        serde_json::from_str(data)
+            .map(Body::Json)
+            .map_err(|e| Error::from(format!("Invalid JSON body: {}", e)))
+    }
+
+    /// The `Content-Type` of the encoded body, if there is one.
+    pub fn content_type(&self) -> Option<String> {
+        match self {
+            Body::Empty => None,
+            Body::Form(_) => Some("application/x-www-form-urlencoded".to_owned()),
+            Body::Json(_) => Some("application/json".to_owned()),
+            Body::Multipart(_) => Some(format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY)),
+        }
+    }
+
+    /// Encodes the body for sending.
+    pub fn encode(&self) -> Option<String> {
+        match self {
+            Body::Empty => None,
+            Body::Form(pairs) => Some(form_urlencoded::Serializer::new(String::new()).extend_pairs(pairs).finish()),
+            Body::Json(value) => Some(value.to_string()),
+            Body::Multipart(parts) => {
+                let mut body = String::new();
+                for part in parts {
+                    body.push_str(&format!("--{}\r\n", MULTIPART_BOUNDARY));
+                    body.push_str(&format!("Content-Disposition: form-data; name=\"{}\"", escape_quoted(&part.name)));
+                    if let Some(filename) = &part.filename {
+                        body.push_str(&format!(
+                            "; filename=\"{}\"\r\nContent-Type: application/octet-stream",
+                            escape_quoted(filename)
+                        ));
+                    }
+                    body.push_str(&format!("\r\n\r\n{}\r\n", part.value));
+                }
+                body.push_str(&format!("--{}--\r\n", MULTIPART_BOUNDARY));
+                Some(body)
+            }
+        }
+    }
+}
+
+// escapes a header parameter the way browsers do for multipart field and
+// file names
+fn escape_quoted(value: &str) -> String {
+    value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+impl RequestTemplate {
+    pub fn new(method: Method, url: Url, body: Body) -> Self {
+        RequestTemplate {
+            method,
+            url,
+            headers: Vec::new(),
+            body,
+        }
+    }
+
+    /// The value of header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets header `name`, replacing any value it already has.
+    pub fn set_header(&mut self, name: &str, value: impl Into<String>) {
+        self.headers.retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_owned(), value.into()));
+    }
+
+    /// A `GET` request for `url`.
+    pub fn get(url: Url) -> Self {
+        RequestTemplate::new(Method::GET, url, Body::Empty)
+    }
+
+    /// The request as it goes over the wire in HTTP/1.1, for reports.
+    pub fn to_raw(&self) -> String {
+        let mut target = self.url.path().to_owned();
+        if let Some(query) = self.url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+
+        let mut raw = format!("{} {} HTTP/1.1\r\n", self.method, target);
+        if let Some(host) = self.url.host_str() {
+            match self.url.port() {
+                Some(port) => raw.push_str(&format!("Host: {}:{}\r\n", host, port)),
+                None => raw.push_str(&format!("Host: {}\r\n", host)),
+            }
+        }
+        for (name, value) in &self.headers {
+            raw.push_str(&format!("{}: {}\r\n", name, value));
+        }
+
+        match (self.body.content_type(), self.body.encode()) {
+            (Some(content_type), Some(body)) => {
+                raw.push_str(&format!("Content-Type: {}\r\n", content_type));
+                raw.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+                raw.push_str(&body);
+            }
+            _ => raw.push_str("\r\n"),
+        }
+        raw
+    }
+}
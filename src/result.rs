@@ -0,0 +1,304 @@
+use reqwest::{Method, StatusCode};
+use std::fmt;
+
+use crate::dom::DomFlow;
+use crate::inject::InjectionPoint;
+use crate::probe::Probe;
+use crate::reflection::Reflection;
+use crate::request::RequestTemplate;
+
+/// How sure the scanner is that a finding is exploitable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+    /// The payload ran in the embedded JavaScript engine.
+    Confirmed,
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+            Confidence::Confirmed => "confirmed",
+        };
+        f.write_str(name)
+    }
+}
+
+/// An injected value that came back in the response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub point: InjectionPoint,
+    pub payload: String,
+    /// The request that carried the payload.
+    pub request: RequestTemplate,
+    pub reflection: Reflection,
+    pub status: StatusCode,
+    /// Whether running the response's scripts called the marker.
+    pub confirmed: bool,
+    /// The part of the response around the reflection.
+    pub snippet: String,
+}
+
+impl Finding {
+    pub fn is_exploitable(&self) -> bool {
+        self.reflection.exploitable
+    }
+
+    /// `Confirmed` when the payload executed, `High` when it changed the
+    /// document, `Low` when it was only reflected as data.
+    pub fn confidence(&self) -> Confidence {
+        if self.confirmed {
+            Confidence::Confirmed
+        } else if self.is_exploitable() {
+            Confidence::High
+        } else {
+            Confidence::Low
+        }
+    }
+}
+
+/// A tagged payload that showed up on an observation page after being
+/// injected elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredFinding {
+    /// The injection request that planted the payload.
+    pub request: RequestTemplate,
+    pub point: InjectionPoint,
+    pub payload: String,
+    /// The observation URL the payload showed up on.
+    pub observed_url: String,
+    pub reflection: Reflection,
+    pub status: StatusCode,
+    pub confirmed: bool,
+    /// The part of the observation page around the reflection.
+    pub snippet: String,
+}
+
+impl StoredFinding {
+    pub fn is_exploitable(&self) -> bool {
+        self.reflection.exploitable
+    }
+
+    /// Same as [`Finding::confidence`], for the observation page.
+    pub fn confidence(&self) -> Confidence {
+        if self.confirmed {
+            Confidence::Confirmed
+        } else if self.is_exploitable() {
+            Confidence::High
+        } else {
+            Confidence::Low
+        }
+    }
+}
+
+/// A probe or payload the scanner gave up on, because the host kept
+/// throttling or blocking it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    pub point: InjectionPoint,
+    /// The payload that was pushed back on, or `None` for the probe.
+    pub payload: Option<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.payload {
+            Some(payload) => write!(f, "Gave up on payload '{}' via {}: {}", payload, self.point, self.reason),
+            None => write!(f, "Gave up on {}: {}", self.point, self.reason),
+        }
+    }
+}
+
+/// The outcome of scanning a single URL.
+#[derive(Debug, Clone)]
+pub struct ScanResult {
+    pub url: String,
+    /// The method the URL was scanned with.
+    pub method: Option<Method>,
+    pub status: Option<StatusCode>,
+    /// Hidden parameters found by parameter mining, which were scanned too.
+    pub discovered: Vec<String>,
+    pub findings: Vec<Finding>,
+    /// The injection points whose canary was reflected.
+    pub probes: Vec<Probe>,
+    /// Source-to-sink flows found by statically analyzing the page scripts.
+    pub dom_flows: Vec<DomFlow>,
+    /// Payloads injected into this URL that showed up on observation pages.
+    pub stored: Vec<StoredFinding>,
+    /// Probes and payloads the host kept pushing back on.
+    pub skipped: Vec<Skipped>,
+    /// Why the scan failed or stopped early; findings made before that are
+    /// kept.
+    pub error: Option<String>,
+}
+
+impl ScanResult {
+    pub fn new(url: impl Into<String>) -> Self {
+        ScanResult {
+            url: url.into(),
+            method: None,
+            status: None,
+            discovered: Vec::new(),
+            findings: Vec::new(),
+            probes: Vec::new(),
+            dom_flows: Vec::new(),
+            stored: Vec::new(),
+            skipped: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn error(url: impl Into<String>, error: impl Into<String>) -> Self {
+        ScanResult {
+            error: Some(error.into()),
+            ..ScanResult::new(url)
+        }
+    }
+
+    pub fn is_vulnerable(&self) -> bool {
+        self.findings.iter().any(Finding::is_exploitable)
+            || self.stored.iter().any(StoredFinding::is_exploitable)
+            || !self.dom_flows.is_empty()
+    }
+}
+
+impl fmt::Display for ScanResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = Vec::new();
+
+        if !self.discovered.is_empty() {
+            lines.push(format!("Discovered hidden parameters: {}", self.discovered.join(", ")));
+        }
+
+        for probe in &self.probes {
+            let contexts: Vec<String> = probe.reflections.iter().map(|r| r.context.to_string()).collect();
+            let mut line = format!("Canary reflected in {} via {}", contexts.join(", "), probe.point);
+            if let Some(chars) = &probe.chars {
+                line.push_str(&format!(" [{}]", chars));
+            }
+            lines.push(line);
+        }
+
+        for finding in &self.findings {
+            if finding.confirmed {
+                lines.push(format!(
+                    "Confirmed XSS! Payload '{}' executed in {} via {} ({})",
+                    finding.payload, finding.reflection.context, finding.point, finding.status
+                ));
+            } else if finding.is_exploitable() {
+                lines.push(format!(
+                    "Potential XSS found! Payload '{}' reflected in {} via {} ({})",
+                    finding.payload, finding.reflection.context, finding.point, finding.status
+                ));
+            } else {
+                lines.push(format!(
+                    "Payload '{}' reflected in {} via {}, not exploitable ({})",
+                    finding.payload, finding.reflection.context, finding.point, finding.status
+                ));
+            }
+        }
+
+        for flow in &self.dom_flows {
+            lines.push(format!(
+                "Potential DOM XSS found! {} flows into {} in {} ({} confidence)",
+                flow.source, flow.sink, flow.script, flow.confidence
+            ));
+        }
+
+        for stored in &self.stored {
+            let planted = format!("{} ({} {})", stored.point, stored.request.method, stored.request.url);
+            if stored.confirmed {
+                lines.push(format!(
+                    "Confirmed stored XSS! Payload '{}' planted via {} executed in {} at {} ({})",
+                    stored.payload, planted, stored.reflection.context, stored.observed_url, stored.status
+                ));
+            } else if stored.is_exploitable() {
+                lines.push(format!(
+                    "Potential stored XSS found! Payload '{}' planted via {} shows up in {} at {} ({})",
+                    stored.payload, planted, stored.reflection.context, stored.observed_url, stored.status
+                ));
+            } else {
+                lines.push(format!(
+                    "Stored payload '{}' planted via {} shows up in {} at {}, not exploitable ({})",
+                    stored.payload, planted, stored.reflection.context, stored.observed_url, stored.status
+                ));
+            }
+        }
+
+        lines.extend(self.skipped.iter().map(ToString::to_string));
+
+        if let Some(error) = &self.error {
+            lines.push(format!("Error: {}", error));
+        }
+
+        // a partial scan isn't reported as clean
+        let partial = !self.skipped.is_empty() || self.error.is_some();
+        if self.findings.is_empty() && self.stored.is_empty() && self.dom_flows.is_empty() && !partial {
+            lines.push(match self.status {
+                Some(status) => format!("No tag reflection found ({})", status),
+                None => "No injectable parameters found".to_owned(),
+            });
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{} -> {}", self.url, line)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::find_flows;
+
+    #[test]
+    fn dom_flows_are_not_reported_as_no_reflection() {
+        let mut result = ScanResult::new("https://example.com/");
+        result.status = Some(reqwest::StatusCode::OK);
+        assert!(result.to_string().contains("No tag reflection found"));
+
+        result.dom_flows = find_flows("document.body.innerHTML = location.hash;", "inline script #1");
+        assert!(!result.dom_flows.is_empty());
+        let text = result.to_string();
+        assert!(text.contains("Potential DOM XSS found!"));
+        assert!(!text.contains("No tag reflection found"));
+    }
+
+    #[test]
+    fn skipped_points_are_reported() {
+        let mut result = ScanResult::new("https://example.com/?q=1");
+        result.status = Some(reqwest::StatusCode::OK);
+        result.skipped.push(Skipped {
+            point: InjectionPoint::Query { name: "q".to_owned(), index: 0 },
+            payload: None,
+            reason: "example.com kept pushing back after 4 retries: 429 Too Many Requests".to_owned(),
+        });
+        let text = result.to_string();
+        assert!(text.contains("Gave up on query parameter 'q': example.com kept pushing back"));
+        assert!(!text.contains("No tag reflection found"));
+    }
+
+    #[test]
+    fn errors_keep_earlier_findings() {
+        let error = ScanResult::error("https://example.com/", "connection refused");
+        assert_eq!(error.to_string(), "https://example.com/ -> Error: connection refused");
+
+        let mut result = ScanResult::new("https://example.com/");
+        result.dom_flows = find_flows("document.body.innerHTML = location.hash;", "inline script #1");
+        result.error = Some("connection refused".to_owned());
+        let text = result.to_string();
+        assert!(text.contains("Potential DOM XSS found!"));
+        assert!(text.contains("https://example.com/ -> Error: connection refused"));
+    }
+}
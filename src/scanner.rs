@@ -0,0 +1,1048 @@
+use futures::stream::{self, Stream, StreamExt};
+use futures::FutureExt;
+use reqwest::Method;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use url::Url;
+use urlencoding::decode;
+
+use crate::confirm::executes;
+use crate::cookies::{format_cookie_header, parse_cookie_header, Cookie};
+use crate::crawl::{extract_links, is_crawlable, request_key};
+use crate::forms::extract_forms;
+use crate::dom::{find_flows, inline_scripts, linked_scripts};
+use crate::inject::{cookie_points, injection_points, path_points, InjectionPoint};
+use crate::mine::{page_params, strip_params, with_params, wordlist, Bisection, BATCH_SIZE, DEFAULT_WORDLIST};
+use crate::probe::{canary, char_matrix, char_probe, Probe};
+use crate::payloads::{generate, marker, Payload};
+use crate::reflection::{analyze, analyze_with, excerpt, signatures, Signature};
+use crate::request::{Body, RequestTemplate};
+use crate::result::{Finding, ScanResult, Skipped, StoredFinding};
+use crate::throttle::{rate_interval, retry_after, throttle_reason, Backoff, RateLimiter, MAX_RETRIES};
+use crate::{Error, ErrorKind, Result};
+
+const DEFAULT_CONCURRENCY: usize = 5;
+const DEFAULT_OBSERVE_DELAY: Duration = Duration::from_secs(10);
+
+/// Checks URLs for reflected XSS, running up to `concurrency` requests at once.
+///
+/// Cloning a `Scanner` is cheap: clones share the same client and settings.
+#[derive(Clone)]
+pub struct Scanner {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    client: reqwest::Client,
+    headers: Vec<(String, String)>,
+    method: Method,
+    body: Body,
+    injected_headers: Vec<String>,
+    // cookies only sent to the requests they match
+    cookies: Vec<Cookie>,
+    inject_cookies: bool,
+    inject_path: bool,
+    payloads: Vec<Payload>,
+    passive: bool,
+    dom: bool,
+    observe: Vec<String>,
+    observe_delay: Duration,
+    mine: bool,
+    wordlist: Vec<String>,
+    forms: bool,
+    concurrency: usize,
+    limiter: RateLimiter,
+    backoff: Backoff,
+    // linked scripts already analyzed, so shared scripts are reported once
+    seen_scripts: Mutex<HashSet<String>>,
+    // forms already scanned, so forms shared by several pages are scanned once
+    seen_forms: Mutex<HashSet<String>>,
+    // stored XSS payloads waiting to be looked for on the observation pages
+    planted: Mutex<Vec<Planted>>,
+}
+
+// a tagged payload planted by a stored XSS check
+struct Planted {
+    // the scanned request the payload was planted through
+    url: String,
+    request: RequestTemplate,
+    point: InjectionPoint,
+    tag: String,
+    payload: Payload,
+}
+
+/// Configures a [`Scanner`].
+pub struct ScannerBuilder {
+    client: Option<reqwest::Client>,
+    headers: Vec<(String, String)>,
+    method: Option<Method>,
+    body: Body,
+    cookies: Vec<Cookie>,
+    injected_headers: Vec<String>,
+    inject_cookies: bool,
+    inject_path: bool,
+    payloads: Vec<Payload>,
+    passive: bool,
+    dom: bool,
+    observe: Vec<String>,
+    observe_delay: Duration,
+    mine: bool,
+    wordlist: Vec<String>,
+    forms: bool,
+    concurrency: usize,
+    rate: Option<f64>,
+    host_rate: Option<f64>,
+}
+
+impl Default for ScannerBuilder {
+    fn default() -> Self {
+        ScannerBuilder {
+            client: None,
+            headers: Vec::new(),
+            method: None,
+            body: Body::Empty,
+            cookies: Vec::new(),
+            injected_headers: Vec::new(),
+            inject_cookies: false,
+            inject_path: false,
+            payloads: Vec::new(),
+            passive: false,
+            dom: false,
+            observe: Vec::new(),
+            observe_delay: DEFAULT_OBSERVE_DELAY,
+            mine: false,
+            wordlist: Vec::new(),
+            forms: false,
+            concurrency: DEFAULT_CONCURRENCY,
+            rate: None,
+            host_rate: None,
+        }
+    }
+}
+
+impl ScannerBuilder {
+    /// Uses `client` for every request instead of a default `reqwest::Client`.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Adds a header sent with every request.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Adds a header given as `Name: value`. Malformed headers are ignored.
+    pub fn raw_header(self, header: &str) -> Self {
+        match parse_header(header) {
+            Some((name, value)) => self.header(name, value),
+            None => self,
+        }
+    }
+
+    /// Sets the HTTP method of every request. Defaults to `POST` when a body
+    /// is set and `GET` otherwise.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Sends `body` with every request. Its parameters are injection points
+    /// just like the query parameters.
+    pub fn body(mut self, body: Body) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Adds a cookie sent with every request, on top of any `Cookie` header.
+    pub fn cookie(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.add_cookie(Cookie::new(name, value))
+    }
+
+    /// Adds a cookie sent, and injected into, only with the requests it
+    /// [matches](Cookie::matches).
+    pub fn add_cookie(mut self, cookie: Cookie) -> Self {
+        self.cookies.push(cookie);
+        self
+    }
+
+    /// Also injects payloads into each cookie in turn.
+    pub fn inject_cookies(mut self, inject_cookies: bool) -> Self {
+        self.inject_cookies = inject_cookies;
+        self
+    }
+
+    /// Also injects payloads into each segment of the URL path, and into an
+    /// extra segment appended to it.
+    pub fn inject_path(mut self, inject_path: bool) -> Self {
+        self.inject_path = inject_path;
+        self
+    }
+
+    /// Also injects payloads into each of these request headers, one header
+    /// at a time. See [`DEFAULT_INJECTED_HEADERS`](crate::inject::DEFAULT_INJECTED_HEADERS)
+    /// for a sensible default.
+    pub fn inject_headers<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.injected_headers.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds payloads to inject into every reflected parameter, one request
+    /// per (parameter, payload) pair. Without payloads, the scanner generates
+    /// them from the context and surviving characters of each reflection.
+    pub fn payloads<I, S>(mut self, payloads: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.payloads.extend(payloads.into_iter().map(Payload::custom));
+        self
+    }
+
+    /// Only checks whether the values already present in each URL are
+    /// reflected, without sending any requests of its own.
+    pub fn passive(mut self, passive: bool) -> Self {
+        self.passive = passive;
+        self
+    }
+
+    /// Also statically analyzes the inline and same-host scripts of each page
+    /// for flows from DOM sources such as `location.hash` into sinks such as
+    /// `innerHTML`.
+    pub fn dom(mut self, dom: bool) -> Self {
+        self.dom = dom;
+        self
+    }
+
+    /// Looks for stored XSS: a uniquely tagged payload is injected into every
+    /// injection point, and once every request has been scanned these
+    /// observation URLs are fetched for the tags, once right away and once
+    /// after the observe delay. See [`Scanner::check_stored`].
+    pub fn observe<I, S>(mut self, urls: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.observe.extend(urls.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets how long to wait before fetching the observation URLs a second
+    /// time. Defaults to 10 seconds.
+    pub fn observe_delay(mut self, delay: Duration) -> Self {
+        self.observe_delay = delay;
+        self
+    }
+
+    /// Mines hidden parameters before scanning: names from the wordlist are
+    /// sent in batches, and the ones that are reflected or change the
+    /// response are scanned along with the request's own parameters.
+    pub fn mine(mut self, mine: bool) -> Self {
+        self.mine = mine;
+        self
+    }
+
+    /// Adds parameter names to mine for. Without any, the built-in
+    /// [`DEFAULT_WORDLIST`](crate::mine::DEFAULT_WORDLIST) is used.
+    pub fn wordlist<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.wordlist.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Also scans the forms on the page of each URL passed to
+    /// [`Scanner::scan`], right after the URL itself. A form found on several
+    /// pages is scanned once.
+    pub fn forms(mut self, forms: bool) -> Self {
+        self.forms = forms;
+        self
+    }
+
+    /// Sets how many URLs are checked concurrently.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Caps the number of requests per second across all hosts. Requests
+    /// are spaced evenly rather than sent in bursts. A rate that isn't
+    /// positive, or is below one request a day, makes [`build`](Self::build)
+    /// fail.
+    pub fn rate(mut self, rate: f64) -> Self {
+        self.rate = Some(rate);
+        self
+    }
+
+    /// Caps the number of requests per second to any single host, on top of
+    /// the overall [`rate`](Self::rate), with the same bounds.
+    pub fn host_rate(mut self, rate: f64) -> Self {
+        self.host_rate = Some(rate);
+        self
+    }
+
+    /// Builds the scanner, or fails when a [`rate`](Self::rate) or
+    /// [`host_rate`](Self::host_rate) can't space requests out.
+    pub fn build(self) -> Result<Scanner> {
+        for (name, rate) in [("rate", self.rate), ("host rate", self.host_rate)] {
+            if let Some(rate) = rate.filter(|rate| rate_interval(*rate).is_none()) {
+                return Err(Error::from(format!(
+                    "Invalid {}: {}, expected a positive number of requests per second, at least one a day",
+                    name, rate
+                )));
+            }
+        }
+
+        Ok(Scanner {
+            inner: Arc::new(Inner {
+                client: self.client.unwrap_or_default(),
+                headers: self.headers,
+                method: self.method.unwrap_or(match self.body {
+                    Body::Empty => Method::GET,
+                    _ => Method::POST,
+                }),
+                body: self.body,
+                injected_headers: self.injected_headers,
+                cookies: self.cookies,
+                inject_cookies: self.inject_cookies,
+                inject_path: self.inject_path,
+                payloads: self.payloads,
+                passive: self.passive,
+                dom: self.dom,
+                observe: self.observe,
+                observe_delay: self.observe_delay,
+                mine: self.mine,
+                wordlist: match self.wordlist.is_empty() {
+                    true => wordlist(DEFAULT_WORDLIST),
+                    false => self.wordlist,
+                },
+                forms: self.forms,
+                concurrency: self.concurrency.max(1),
+                limiter: RateLimiter::new(self.rate, self.host_rate),
+                backoff: Backoff::new(self.concurrency),
+                seen_scripts: Mutex::new(HashSet::new()),
+                seen_forms: Mutex::new(HashSet::new()),
+                planted: Mutex::new(Vec::new()),
+            }),
+        })
+    }
+}
+
+/// Splits a `Name: value` header into its trimmed name and value.
+pub fn parse_header(header: &str) -> Option<(String, String)> {
+    let (name, value) = header.split_once(':')?;
+    Some((name.trim().to_owned(), value.trim().to_owned()))
+}
+
+impl Scanner {
+    pub fn builder() -> ScannerBuilder {
+        ScannerBuilder::default()
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.inner.concurrency
+    }
+
+    /// Scans every URL, yielding results in the order they complete.
+    ///
+    /// With [`forms`](ScannerBuilder::forms) enabled, the results for a
+    /// page's forms follow the result for its URL.
+    pub fn scan<I>(&self, urls: I) -> impl Stream<Item = ScanResult>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.scan_stream(stream::iter(urls))
+    }
+
+    /// Like [`scan`](Self::scan), but URLs come from a stream, such as the
+    /// lines of stdin. They are only pulled as tasks free up, so the stream
+    /// is never read further ahead than needed.
+    pub fn scan_stream<S>(&self, urls: S) -> impl Stream<Item = ScanResult>
+    where
+        S: Stream<Item = String>,
+    {
+        let scanner = self.clone();
+
+        urls.map(move |url| {
+            let scanner = scanner.clone();
+            let task_url = url.clone();
+            tokio::spawn(async move { scanner.check_page(&url).await }).map(move |joined| {
+                joined.unwrap_or_else(|e| vec![ScanResult::error(task_url, format!("Task error: {:?}", e))])
+            })
+        })
+        .buffer_unordered(self.inner.concurrency)
+        .flat_map(stream::iter)
+        .chain(self.stored_results())
+    }
+
+    /// Scans every request, such as the ones [`crawl`](Self::crawl)
+    /// returns, yielding results in the order they complete.
+    pub fn scan_requests<I>(&self, requests: I) -> impl Stream<Item = ScanResult>
+    where
+        I: IntoIterator<Item = RequestTemplate>,
+    {
+        let scanner = self.clone();
+
+        stream::iter(requests)
+            .map(move |request| {
+                let scanner = scanner.clone();
+                let task_url = request.url.to_string();
+                tokio::spawn(async move { scanner.check_request(&request).await }).map(move |joined| {
+                    joined.unwrap_or_else(|e| ScanResult::error(task_url, format!("Task error: {:?}", e)))
+                })
+            })
+            .buffer_unordered(self.inner.concurrency)
+            .chain(self.stored_results())
+    }
+
+    /// Looks for every stored XSS payload planted so far on the observation
+    /// pages, once right away and once after the observe delay, and returns a
+    /// result with the stored findings of each request that planted one.
+    ///
+    /// [`scan`](Self::scan), [`scan_stream`](Self::scan_stream) and
+    /// [`scan_requests`](Self::scan_requests) do this once every request has
+    /// been scanned, so a tag planted early is still looked for after the
+    /// last one. Call it after [`check`](Self::check) and
+    /// [`check_request`](Self::check_request) to do the same.
+    pub async fn check_stored(&self) -> Vec<ScanResult> {
+        let planted = match self.inner.planted.lock() {
+            Ok(mut planted) => std::mem::take(&mut *planted),
+            Err(_) => return Vec::new(),
+        };
+        if planted.is_empty() {
+            return Vec::new();
+        }
+
+        // one result per planting request, in the order they were planted
+        let mut results: Vec<ScanResult> = Vec::new();
+        for pass in 0..2 {
+            if pass > 0 {
+                tokio::time::sleep(self.inner.observe_delay).await;
+            }
+
+            for url in &self.inner.observe {
+                let Ok(url) = Url::parse(url) else {
+                    continue;
+                };
+                let observation = self.with_headers(RequestTemplate::get(url));
+                // a page that fails to load shouldn't hide what the others show
+                let Ok((status, body)) = self.fetch(&observation).await else {
+                    continue;
+                };
+
+                for plant in &planted {
+                    let observed_url = observation.url.as_str();
+                    let already_found = results
+                        .iter()
+                        .flat_map(|result| &result.stored)
+                        .any(|stored| stored.payload == plant.payload.value && stored.observed_url == observed_url);
+                    if already_found || !body.contains(plant.tag.as_str()) {
+                        continue;
+                    }
+                    // when the payload was mangled, the tag still shows where it landed
+                    let finding = match find_reflection(&plant.point, &plant.payload, &observation, &body, status).await {
+                        Some(finding) => Some(finding),
+                        None => {
+                            let tag = Payload::custom(plant.tag.as_str());
+                            find_reflection(&plant.point, &tag, &observation, &body, status).await
+                        }
+                    };
+                    let Some(finding) = finding else {
+                        continue;
+                    };
+
+                    let method = Some(plant.request.method.clone());
+                    let position = results
+                        .iter()
+                        .position(|result| result.url == plant.url && result.method == method);
+                    let result = match position {
+                        Some(i) => &mut results[i],
+                        None => {
+                            let mut result = ScanResult::new(plant.url.as_str());
+                            result.method = method;
+                            results.push(result);
+                            results.last_mut().expect("just pushed")
+                        }
+                    };
+                    result.stored.push(StoredFinding {
+                        request: plant.request.clone(),
+                        point: finding.point,
+                        payload: plant.payload.value.clone(),
+                        observed_url: observed_url.to_owned(),
+                        reflection: finding.reflection,
+                        status,
+                        confirmed: finding.confirmed,
+                        snippet: finding.snippet,
+                    });
+                }
+            }
+        }
+
+        results
+    }
+
+    // the results of `check_stored`, as a stream that runs it when polled
+    fn stored_results(&self) -> impl Stream<Item = ScanResult> {
+        let scanner = self.clone();
+        stream::once(async move { scanner.check_stored().await }).flat_map(stream::iter)
+    }
+
+    /// Crawls from `seeds`, following links on the seeds' hosts up to `depth`
+    /// hops away, and returns a request for each unique parameterized
+    /// endpoint and form found along the way, seeds included. Pages that fail
+    /// to load are skipped.
+    pub async fn crawl<I>(&self, seeds: I, depth: usize) -> Vec<RequestTemplate>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut frontier: Vec<Url> = seeds.into_iter().filter_map(|seed| Url::parse(&seed).ok()).collect();
+        let hosts: HashSet<String> = frontier.iter().filter_map(|url| url.host_str().map(str::to_owned)).collect();
+
+        let mut visited: HashSet<Url> = frontier.iter().cloned().collect();
+        let mut requests = Vec::new();
+        let mut keys = HashSet::new();
+        let mut record = |request: RequestTemplate| {
+            if keys.insert(request_key(&request)) {
+                requests.push(request);
+            }
+        };
+        frontier
+            .iter()
+            .filter(|url| url.query().is_some())
+            .for_each(|url| record(self.request(url.clone())));
+
+        for _ in 0..depth {
+            let pages = self.fetch_pages(frontier.into_iter().filter(is_crawlable)).await;
+
+            frontier = Vec::new();
+            for (url, body) in pages {
+                extract_forms(&body, &url)
+                    .into_iter()
+                    .filter(|form| form.url.host_str().is_some_and(|host| hosts.contains(host)))
+                    .for_each(|form| record(self.with_headers(form)));
+
+                for link in extract_links(&body, &url) {
+                    let in_scope = link.host_str().is_some_and(|host| hosts.contains(host));
+                    if in_scope && visited.insert(link.clone()) {
+                        if link.query().is_some() {
+                            record(self.request(link.clone()));
+                        }
+                        frontier.push(link);
+                    }
+                }
+            }
+        }
+
+        requests
+    }
+
+    /// Scans a single URL.
+    pub async fn check(&self, url: &str) -> ScanResult {
+        let request = match Url::parse(url) {
+            Ok(parsed) => self.request(parsed),
+            Err(e) => return ScanResult::error(url, format!("{:?}", Error::from(e))),
+        };
+
+        let mut result = self.check_request(&request).await;
+        result.url = url.to_owned();
+        result
+    }
+
+    /// Scans a single request.
+    pub async fn check_request(&self, request: &RequestTemplate) -> ScanResult {
+        let mut result = match self.check_xss_reflection(request).await {
+            Ok(result) => result,
+            Err(e) => ScanResult::error(request.url.as_str(), format!("{:?}", e)),
+        };
+        result.method = Some(request.method.clone());
+        result
+    }
+
+    // scans `url`, then the forms on its page that haven't been scanned yet
+    async fn check_page(&self, url: &str) -> Vec<ScanResult> {
+        let mut results = vec![self.check(url).await];
+        if self.inner.forms {
+            for form in self.page_forms(url).await {
+                results.push(self.check_request(&form).await);
+            }
+        }
+        results
+    }
+
+    // the unseen forms on the page at `url`, or none if it fails to load
+    async fn page_forms(&self, url: &str) -> Vec<RequestTemplate> {
+        let Ok(url) = Url::parse(url) else {
+            return Vec::new();
+        };
+        let request = self.with_headers(RequestTemplate::get(url.clone()));
+        let Ok((_, body)) = self.fetch(&request).await else {
+            return Vec::new();
+        };
+
+        extract_forms(&body, &url)
+            .into_iter()
+            .filter(|form| {
+                self.inner
+                    .seen_forms
+                    .lock()
+                    .map(|mut seen| seen.insert(request_key(form)))
+                    .unwrap_or(false)
+            })
+            .map(|form| self.with_headers(form))
+            .collect()
+    }
+
+    // a request for `url` with the configured method, body and headers
+    fn request(&self, url: Url) -> RequestTemplate {
+        self.with_headers(RequestTemplate::new(self.inner.method.clone(), url, self.inner.body.clone()))
+    }
+
+    // `request` with the configured headers, and the cookies that match it
+    // added to its `Cookie` header
+    fn with_headers(&self, mut request: RequestTemplate) -> RequestTemplate {
+        request.headers = self.inner.headers.clone();
+
+        let matching: Vec<(String, String)> = self
+            .inner
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.matches(&request.url))
+            .map(|cookie| (cookie.name.clone(), cookie.value.clone()))
+            .collect();
+        if !matching.is_empty() {
+            let mut cookies = parse_cookie_header(request.header("Cookie").unwrap_or_default());
+            cookies.extend(matching);
+            request.set_header("Cookie", format_cookie_header(&cookies));
+        }
+
+        request
+    }
+
+    // GETs every page in `urls` concurrently, skipping the ones that fail
+    async fn fetch_pages(&self, urls: impl Iterator<Item = Url>) -> Vec<(Url, String)> {
+        stream::iter(urls)
+            .map(|url| async move {
+                let request = self.with_headers(RequestTemplate::get(url.clone()));
+                self.fetch(&request).await.ok().map(|(_, body)| (url, body))
+            })
+            .buffer_unordered(self.inner.concurrency)
+            .filter_map(|page| async move { page })
+            .collect()
+            .await
+    }
+
+    async fn check_xss_reflection(&self, request: &RequestTemplate) -> Result<ScanResult> {
+        let url = request.url.as_str();
+
+        let (request, discovered) = if self.inner.mine && !self.inner.passive {
+            let names = self.mine_params(request).await?;
+            (with_params(request, names.iter().map(|name| (name.as_str(), "1"))), names)
+        } else {
+            (request.clone(), Vec::new())
+        };
+        let request = &request;
+        let mut result = if self.inner.passive {
+            self.check_existing_values(url, request).await?
+        } else {
+            self.check_injections(url, request).await
+        };
+
+        if self.inner.dom {
+            self.check_dom(request, &mut result).await;
+        }
+
+        if !self.inner.passive && !self.inner.observe.is_empty() && result.error.is_none() {
+            if let Err(e) = self.plant(request, &mut result).await {
+                result.error = Some(format!("{:?}", e));
+            }
+        }
+
+        result.discovered = discovered;
+
+        Ok(result)
+    }
+
+    // traces sources to sinks in the inline and same-host scripts of the
+    // page; a page that fails to load here keeps the findings already made
+    async fn check_dom(&self, request: &RequestTemplate, result: &mut ScanResult) {
+        let Ok((status, body)) = self.fetch(request).await else {
+            return;
+        };
+        result.status.get_or_insert(status);
+
+        for (i, script) in inline_scripts(&body).iter().enumerate() {
+            result
+                .dom_flows
+                .extend(find_flows(script, &format!("inline script #{}", i + 1)));
+        }
+
+        for script_url in linked_scripts(&body, &request.url) {
+            let first_seen = self
+                .inner
+                .seen_scripts
+                .lock()
+                .map(|mut seen| seen.insert(script_url.to_string()))
+                .unwrap_or(false);
+            if !first_seen {
+                continue;
+            }
+
+            let script_request = self.with_headers(RequestTemplate::get(script_url.clone()));
+
+            // a script that fails to load shouldn't fail the whole page
+            if let Ok((status, source)) = self.fetch(&script_request).await {
+                if status.is_success() {
+                    result.dom_flows.extend(find_flows(&source, script_url.as_str()));
+                }
+            }
+        }
+    }
+
+    // finds the names the endpoint reads, from its own page and the wordlist,
+    // by sending them in batches with unique canaries: reflected names are
+    // found by their canary, and batches that change the response are
+    // bisected down to single names
+    async fn mine_params(&self, request: &RequestTemplate) -> Result<Vec<String>> {
+        let (baseline_status, baseline) = self.fetch(request).await?;
+
+        // names from the page itself are the likeliest, so they go first
+        let mut seen: HashSet<String> = self
+            .injection_points(request)
+            .iter()
+            .map(|point| point.name().to_owned())
+            .collect();
+        let candidates: Vec<String> = page_params(&baseline)
+            .into_iter()
+            .chain(self.inner.wordlist.iter().cloned())
+            .filter(|name| seen.insert(name.clone()))
+            .collect();
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (_, again) = self.fetch(request).await?;
+        // pages whose length varies on its own can only be compared by status
+        let stable = baseline.len() == again.len();
+
+        let mut found: Vec<String> = Vec::new();
+        for batch in candidates.chunks(BATCH_SIZE) {
+            let mut bisection = Bisection::new(batch);
+
+            while let Some(params) = bisection.next_batch() {
+                let mined = with_params(request, params.iter().map(|(name, value)| (name.as_str(), value.as_str())));
+                let (status, body) = self.fetch(&mined).await?;
+
+                let changed = status != baseline_status || (stable && strip_params(&body, &params).len() != baseline.len());
+                bisection.record(params, &body, changed);
+            }
+
+            found.extend(bisection.found());
+        }
+
+        Ok(found)
+    }
+
+    // plants a tagged payload in every injection point, to be looked for
+    // by `check_stored` once every request has been scanned
+    async fn plant(&self, request: &RequestTemplate, result: &mut ScanResult) -> Result<()> {
+        for point in self.injection_points(request) {
+            let tag = canary();
+            let mut payload = Payload::custom(format!("'\"><svg onload=alert({}) class={}>", marker(), tag));
+            // only the element carries the tag, other plants share the handler
+            payload
+                .signatures
+                .retain(|signature| matches!(signature, Signature::Element { .. }));
+            let injected = point.apply(request, &payload.value);
+            let status = match self.fetch(&injected).await {
+                Ok((status, _)) => status,
+                Err(e) => {
+                    skip(result, &point, Some(&payload.value), e)?;
+                    continue;
+                }
+            };
+            result.status.get_or_insert(status);
+
+            if let Ok(mut planted) = self.inner.planted.lock() {
+                planted.push(Planted {
+                    url: request.url.to_string(),
+                    request: injected,
+                    point,
+                    tag,
+                    payload,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // checks whether the values already in the request are reflected
+    async fn check_existing_values(&self, url: &str, request: &RequestTemplate) -> Result<ScanResult> {
+        let (status, body) = self.fetch(request).await?;
+        let mut result = ScanResult::new(url);
+        result.status = Some(status);
+
+        for point in self.injection_points(request) {
+            let Some(value) = point.value(request) else {
+                continue;
+            };
+            let decoded_value = decode(&value).map_err(|e| Error::from(format!("Decoding error: {}", e)))?;
+            if signatures(&decoded_value).is_empty() {
+                continue;
+            }
+            let payload = Payload::custom(decoded_value);
+            if let Some(finding) = find_reflection(&point, &payload, request, &body, status).await {
+                result.findings.push(finding);
+                return Ok(result);
+            }
+        }
+
+        Ok(result)
+    }
+
+    // probes every parameter with a canary first, then sends one request per
+    // (parameter, payload) pair for the parameters that were reflected; a
+    // request that fails for any other reason than throttling stops the scan,
+    // keeping what was found so far
+    async fn check_injections(&self, url: &str, request: &RequestTemplate) -> ScanResult {
+        let mut result = ScanResult::new(url);
+        if let Err(e) = self.inject_points(request, &mut result).await {
+            result.error = Some(format!("{:?}", e));
+        }
+        result
+    }
+
+    // the scan behind `check_injections`; a probe or payload the host keeps
+    // pushing back on is recorded as skipped and the scan goes on
+    async fn inject_points(&self, request: &RequestTemplate, result: &mut ScanResult) -> Result<()> {
+        for point in self.injection_points(request) {
+            let probe = match self.probe(request, &point, result).await {
+                Ok(Some(probe)) => probe,
+                Ok(None) => continue,
+                Err(e) => {
+                    skip(result, &point, None, e)?;
+                    continue;
+                }
+            };
+
+            if self.inner.payloads.is_empty() {
+                // generated payloads stop at the first one that is confirmed,
+                // falling back to the first exploitable one if none is
+                let mut best: Option<Finding> = None;
+                for payload in generated_payloads(&probe) {
+                    let injected = point.apply(request, &payload.value);
+                    let (status, body) = match self.fetch(&injected).await {
+                        Ok(response) => response,
+                        Err(e) => {
+                            skip(result, &point, Some(&payload.value), e)?;
+                            continue;
+                        }
+                    };
+                    result.status = Some(status);
+
+                    if let Some(finding) = find_reflection(&point, &payload, &injected, &body, status).await {
+                        if finding.confirmed {
+                            best = Some(finding);
+                            break;
+                        }
+                        if finding.is_exploitable() {
+                            best.get_or_insert(finding);
+                        }
+                    }
+                }
+                result.findings.extend(best);
+            } else {
+                for payload in &self.inner.payloads {
+                    let injected = point.apply(request, &payload.value);
+                    let (status, body) = match self.fetch(&injected).await {
+                        Ok(response) => response,
+                        Err(e) => {
+                            skip(result, &point, Some(&payload.value), e)?;
+                            continue;
+                        }
+                    };
+                    result.status = Some(status);
+
+                    if let Some(finding) = find_reflection(&point, payload, &injected, &body, status).await {
+                        result.findings.push(finding);
+                    }
+                }
+            }
+
+            result.probes.push(probe);
+        }
+
+        Ok(())
+    }
+
+    // the injection points of `request`, plus the path segments, headers
+    // and cookies when enabled
+    fn injection_points(&self, request: &RequestTemplate) -> Vec<InjectionPoint> {
+        let mut points = injection_points(request);
+        if self.inner.inject_path {
+            points.extend(path_points(request));
+        }
+        points.extend(
+            self.inner
+                .injected_headers
+                .iter()
+                .map(|name| InjectionPoint::Header { name: name.clone() }),
+        );
+        if self.inner.inject_cookies {
+            points.extend(cookie_points(request));
+        }
+        points
+    }
+
+    // injects a canary into `point` and records where it was reflected, then
+    // checks which special characters survive the reflection
+    async fn probe(
+        &self,
+        request: &RequestTemplate,
+        point: &InjectionPoint,
+        result: &mut ScanResult,
+    ) -> Result<Option<Probe>> {
+        let canary = canary();
+        let (status, body) = self.fetch(&point.apply(request, &canary)).await?;
+        result.status = Some(status);
+
+        let reflections = analyze(&body, &canary);
+        if reflections.is_empty() {
+            return Ok(None);
+        }
+
+        let (status, body) = self.fetch(&point.apply(request, &char_probe(&canary))).await?;
+        result.status = Some(status);
+
+        Ok(Some(Probe {
+            point: point.clone(),
+            chars: char_matrix(&body, &canary),
+            canary,
+            reflections,
+        }))
+    }
+
+    // every request goes through here, so every mode respects the rate limits
+    // and backs off hosts that push back, retrying rather than reporting the
+    // throttled response
+    async fn fetch(&self, template: &RequestTemplate) -> Result<(reqwest::StatusCode, String)> {
+        let mut retries = 0;
+        loop {
+            let permit = self.inner.backoff.acquire(&template.url).await;
+            self.inner.limiter.wait(&template.url).await;
+
+            let (status, retry_after, body) = self.send(template).await?;
+            let Some(reason) = throttle_reason(status, &body) else {
+                permit.succeeded();
+                return Ok((status, body));
+            };
+
+            permit.throttled(retry_after);
+            if retries == MAX_RETRIES {
+                let host = template.url.host_str().unwrap_or_default().to_owned();
+                return Err(ErrorKind::Throttled(host, reason).into());
+            }
+            retries += 1;
+        }
+    }
+
+    async fn send(
+        &self,
+        template: &RequestTemplate,
+    ) -> Result<(reqwest::StatusCode, Option<Duration>, String)> {
+        let mut request = self
+            .inner
+            .client
+            .request(template.method.clone(), template.url.as_str());
+
+        for (name, value) in &template.headers {
+            request = request.header(name, value);
+        }
+
+        if let Some(body) = template.body.encode() {
+            if let Some(content_type) = template.body.content_type() {
+                request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+            }
+            request = request.body(body);
+        }
+
+        let resp = request.send().await?;
+        let status = resp.status();
+        let retry_after = retry_after(resp.headers());
+        let body = resp.text().await?;
+
+        Ok((status, retry_after, body))
+    }
+}
+
+// records that `point`, or one of its payloads, was given up on because
+// the host kept pushing back; any other error is passed on
+fn skip(result: &mut ScanResult, point: &InjectionPoint, payload: Option<&str>, error: Error) -> Result<()> {
+    let ErrorKind::Throttled(..) = error.kind() else {
+        return Err(error);
+    };
+    result.skipped.push(Skipped {
+        point: point.clone(),
+        payload: payload.map(str::to_owned),
+        reason: error.to_string(),
+    });
+    Ok(())
+}
+
+// generates payloads for every context the canary was reflected in
+fn generated_payloads(probe: &Probe) -> Vec<Payload> {
+    let mut payloads: Vec<Payload> = Vec::new();
+    let marker = marker();
+
+    for reflection in &probe.reflections {
+        for payload in generate(reflection, probe.chars.as_ref(), marker) {
+            if !payloads.iter().any(|p| p.value == payload.value) {
+                payloads.push(payload);
+            }
+        }
+    }
+
+    payloads
+}
+
+// reports the most significant reflection of `payload`, preferring the
+// ones that changed the document structure, and runs the page's scripts to
+// confirm exploitable ones
+async fn find_reflection(
+    point: &InjectionPoint,
+    payload: &Payload,
+    request: &RequestTemplate,
+    body: &str,
+    status: reqwest::StatusCode,
+) -> Option<Finding> {
+    let reflection = analyze_with(body, &payload.value, &payload.signatures)
+        .into_iter()
+        .min_by_key(|reflection| !reflection.exploitable)?;
+
+    Some(Finding {
+        point: point.clone(),
+        payload: payload.value.clone(),
+        request: request.clone(),
+        confirmed: reflection.exploitable && confirm(body, payload).await,
+        snippet: excerpt(body, &payload.value),
+        reflection,
+        status,
+    })
+}
+
+// runs the scripts on the blocking pool, so a heavy page doesn't stall the
+// other scans and the rate limit timers
+async fn confirm(body: &str, payload: &Payload) -> bool {
+    let (body, payload) = (body.to_owned(), payload.clone());
+    tokio::task::spawn_blocking(move || executes(&body, &payload))
+        .await
+        .unwrap_or(false)
+}
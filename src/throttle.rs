@@ -0,0 +1,408 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+use url::Url;
+
+/// How many times a throttled request is retried before giving up.
+pub const MAX_RETRIES: u32 = 4;
+
+// how long a host is left alone after pushing back without a Retry-After,
+// doubling with each push back in a row
+const BASE_BACKOFF: Duration = Duration::from_secs(2);
+
+// the longest a host is left alone, whatever its Retry-After says
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+// the longest wait between two requests a rate may ask for, so the next
+// slot of a token bucket always fits in an `Instant`
+const MAX_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// successful requests in a row it takes to give a host back one slot
+const RECOVERY_SUCCESSES: usize = 10;
+
+// block page statuses and text of common web application firewalls
+const BLOCK_STATUSES: [u16; 2] = [403, 406];
+const BLOCK_SIGNATURES: [&str; 10] = [
+    "Attention Required! | Cloudflare",
+    "cf-error-details",
+    "Sorry, you have been blocked",
+    "Access Denied - Sucuri Website Firewall",
+    "Request unsuccessful. Incapsula incident ID",
+    "The requested URL was rejected. Please consult with your administrator.",
+    "You don't have permission to access \"http",
+    "This error was generated by Mod_Security",
+    "Request blocked by Web Application Firewall",
+    "AwsWafIntegration",
+];
+
+/// A token bucket refilled at `rate` tokens per second, holding at most one
+/// token, so requests are spaced evenly instead of going out in bursts.
+pub struct TokenBucket {
+    interval: Duration,
+    // when the next token is available; may be in the future when callers
+    // are already queued for it
+    next: Mutex<Instant>,
+}
+
+impl TokenBucket {
+    /// A bucket for `rate` tokens per second, or `None` when `rate` isn't
+    /// valid, see [`rate_interval`].
+    pub fn new(rate: f64) -> Option<Self> {
+        Some(TokenBucket {
+            interval: rate_interval(rate)?,
+            next: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Takes a token, waiting for one if the bucket is empty. Callers are
+    /// served in the order they arrive.
+    pub async fn acquire(&self) {
+        let slot = match self.next.lock() {
+            Ok(mut next) => {
+                let slot = (*next).max(Instant::now());
+                *next = slot + self.interval;
+                slot
+            }
+            Err(_) => return,
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+/// The time between two requests at `rate` requests per second, or `None`
+/// when `rate` isn't positive or is below one request a day.
+pub fn rate_interval(rate: f64) -> Option<Duration> {
+    if rate > 0.0 {
+        Duration::try_from_secs_f64(1.0 / rate)
+            .ok()
+            .filter(|interval| *interval <= MAX_INTERVAL)
+    } else {
+        None
+    }
+}
+
+/// Limits the request rate, both overall and per host.
+#[derive(Default)]
+pub struct RateLimiter {
+    global: Option<TokenBucket>,
+    host_rate: Option<f64>,
+    hosts: Mutex<HashMap<String, Arc<TokenBucket>>>,
+}
+
+impl RateLimiter {
+    /// A limiter allowing `rate` requests per second overall and `host_rate`
+    /// to any single host. `None` means no limit; a rate that isn't valid,
+    /// see [`rate_interval`], is ignored.
+    pub fn new(rate: Option<f64>, host_rate: Option<f64>) -> Self {
+        RateLimiter {
+            global: rate.and_then(TokenBucket::new),
+            host_rate: host_rate.filter(|rate| rate_interval(*rate).is_some()),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until a request to `url` is allowed, first by its host's limit,
+    /// then by the global one.
+    pub async fn wait(&self, url: &Url) {
+        if let Some(bucket) = self.host_bucket(url) {
+            bucket.acquire().await;
+        }
+        if let Some(bucket) = &self.global {
+            bucket.acquire().await;
+        }
+    }
+
+    fn host_bucket(&self, url: &Url) -> Option<Arc<TokenBucket>> {
+        let rate = self.host_rate?;
+        let host = url.host_str()?.to_owned();
+        let mut hosts = self.hosts.lock().ok()?;
+        let bucket = match hosts.get(&host) {
+            Some(bucket) => bucket.clone(),
+            None => hosts.entry(host).or_insert(Arc::new(TokenBucket::new(rate)?)).clone(),
+        };
+        Some(bucket)
+    }
+}
+
+/// Why a host pushed back on a request, if it did: a 429 or 503 status, or
+/// a web application firewall's block page.
+pub fn throttle_reason(status: StatusCode, body: &str) -> Option<String> {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => Some(status.to_string()),
+        _ if BLOCK_STATUSES.contains(&status.as_u16())
+            && BLOCK_SIGNATURES.iter().any(|signature| body.contains(signature)) =>
+        {
+            Some(format!("web application firewall block page ({})", status))
+        }
+        _ => None,
+    }
+}
+
+/// How long the `Retry-After` header in `headers` asks to wait, given
+/// either in seconds or as an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = parse_http_date(value)?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+// an IMF-fixdate such as `Sun, 06 Nov 1994 08:49:37 GMT`
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let fields: Vec<&str> = value.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = fields.as_slice() else {
+        return None;
+    };
+    let day: i64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| m == month)? as i64 + 1;
+    let year: i64 = year.parse().ok()?;
+    let mut clock = time.split(':').map(|part| part.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (clock.next()??, clock.next()??, clock.next()??);
+    // the server picks these, so out of range values are rejected before
+    // they can overflow the arithmetic below
+    let valid = (1970..=9999).contains(&year)
+        && (1..=31).contains(&day)
+        && (0..24).contains(&hours)
+        && (0..60).contains(&minutes)
+        && (0..=60).contains(&seconds);
+    if !valid {
+        return None;
+    }
+
+    // days since the epoch, from Howard Hinnant's days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs = days * 86400 + hours * 3600 + minutes * 60 + seconds;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+/// Per-host concurrency that adapts to how each host copes: a host that
+/// pushes back is paused, as long as its `Retry-After` asks when it gives
+/// one, and its share of concurrent requests is halved. Each run of
+/// successful requests then gives it back one more, up to the maximum.
+pub struct Backoff {
+    max: usize,
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+}
+
+struct Host {
+    state: Mutex<HostState>,
+    // woken whenever a slot frees up or the limit grows
+    released: Notify,
+}
+
+struct HostState {
+    limit: usize,
+    in_flight: usize,
+    // no requests until then
+    resume: Instant,
+    successes: usize,
+    // push backs in a row, for the exponential backoff
+    failures: u32,
+}
+
+/// A slot for one request to a host, given back when dropped.
+pub struct Permit {
+    host: Option<Arc<Host>>,
+    max: usize,
+}
+
+impl Backoff {
+    /// Allows up to `max` concurrent requests to each host while it copes.
+    pub fn new(max: usize) -> Self {
+        Backoff {
+            max: max.max(1),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits until `url`'s host is neither paused nor at its limit, and
+    /// takes one of its slots.
+    pub async fn acquire(&self, url: &Url) -> Permit {
+        let Some(host) = self.host(url) else {
+            return Permit { host: None, max: self.max };
+        };
+
+        loop {
+            // registered before checking, so a release in between isn't missed
+            let released = host.released.notified();
+            let resume = {
+                let Ok(mut state) = host.state.lock() else {
+                    return Permit { host: None, max: self.max };
+                };
+                if Instant::now() < state.resume {
+                    Some(state.resume)
+                } else if state.in_flight < state.limit {
+                    state.in_flight += 1;
+                    break;
+                } else {
+                    None
+                }
+            };
+            match resume {
+                Some(resume) => tokio::time::sleep_until(resume.into()).await,
+                None => released.await,
+            }
+        }
+
+        Permit {
+            host: Some(host),
+            max: self.max,
+        }
+    }
+
+    fn host(&self, url: &Url) -> Option<Arc<Host>> {
+        let name = url.host_str()?.to_owned();
+        let mut hosts = self.hosts.lock().ok()?;
+        let host = hosts.entry(name).or_insert_with(|| {
+            Arc::new(Host {
+                state: Mutex::new(HostState {
+                    limit: self.max,
+                    in_flight: 0,
+                    resume: Instant::now(),
+                    successes: 0,
+                    failures: 0,
+                }),
+                released: Notify::new(),
+            })
+        });
+        Some(host.clone())
+    }
+}
+
+impl Permit {
+    /// Records that the host handled the request.
+    pub fn succeeded(&self) {
+        let Some(mut state) = self.state() else {
+            return;
+        };
+        state.failures = 0;
+        state.successes += 1;
+        if state.successes >= RECOVERY_SUCCESSES && state.limit < self.max {
+            state.limit += 1;
+            state.successes = 0;
+            drop(state);
+            self.notify();
+        }
+    }
+
+    /// Records that the host pushed back, pausing it for `retry_after` if
+    /// given and an exponential backoff otherwise, and halving its limit.
+    /// Returns how long the host is paused for.
+    pub fn throttled(&self, retry_after: Option<Duration>) -> Duration {
+        let Some(mut state) = self.state() else {
+            return Duration::ZERO;
+        };
+        state.failures += 1;
+        state.successes = 0;
+        state.limit = (state.limit / 2).max(1);
+
+        let delay = retry_after
+            .unwrap_or_else(|| BASE_BACKOFF.saturating_mul(1 << (state.failures - 1).min(8)))
+            .min(MAX_BACKOFF);
+        state.resume = state.resume.max(Instant::now() + delay);
+        delay
+    }
+
+    fn state(&self) -> Option<std::sync::MutexGuard<'_, HostState>> {
+        self.host.as_ref()?.state.lock().ok()
+    }
+
+    fn notify(&self) {
+        if let Some(host) = &self.host {
+            host.released.notify_waiters();
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(mut state) = self.state() {
+            state.in_flight -= 1;
+        }
+        self.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn at(secs: u64) -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn rejects_rates_without_an_interval() {
+        assert_eq!(rate_interval(4.0), Some(Duration::from_millis(250)));
+        assert_eq!(rate_interval(0.5), Some(Duration::from_secs(2)));
+        assert_eq!(rate_interval(0.0), None);
+        assert_eq!(rate_interval(-1.0), None);
+        assert_eq!(rate_interval(f64::NAN), None);
+        assert_eq!(rate_interval(1e-30), None);
+        assert_eq!(rate_interval(1e-19), None);
+        assert_eq!(rate_interval(1.0 / 86400.0), Some(MAX_INTERVAL));
+        assert_eq!(rate_interval(1.0 / 86401.0), None);
+        assert!(TokenBucket::new(1e-30).is_none());
+        assert!(TokenBucket::new(0.0).is_none());
+    }
+
+    #[test]
+    fn parses_http_dates() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), at(784111777));
+        assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), at(0));
+        assert_eq!(parse_http_date("Thu, 29 Feb 2024 12:00:00 GMT"), at(1709208000));
+        assert_eq!(parse_http_date("Fri, 31 Dec 2100 23:59:59 GMT"), at(4133980799));
+    }
+
+    #[test]
+    fn rejects_malformed_http_dates() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 PST"), None);
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 99999999999999999 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 99999999999999 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 99999999999999:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:60:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 00 Nov 1994 08:49:37 GMT"), None);
+    }
+
+    #[test]
+    fn reads_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+
+        // dates in the past mean no wait at all
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT"));
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn detects_throttling() {
+        assert!(throttle_reason(StatusCode::TOO_MANY_REQUESTS, "").is_some());
+        assert!(throttle_reason(StatusCode::SERVICE_UNAVAILABLE, "").is_some());
+        assert!(throttle_reason(StatusCode::FORBIDDEN, "<title>Attention Required! | Cloudflare</title>").is_some());
+        assert!(throttle_reason(StatusCode::FORBIDDEN, "<h1>Forbidden</h1>").is_none());
+        assert!(throttle_reason(StatusCode::OK, "Attention Required! | Cloudflare").is_none());
+    }
+}